@@ -12,6 +12,7 @@ export default class LSZL {
     multiply?: number,
     forceInMemoryCache?: boolean,
     forceKeepCache?: boolean,
+    encoding?: string,
   }
   ) {
     const url = new URL(params.url, window.location.href).href;
@@ -25,6 +26,7 @@ export default class LSZL {
         noUseCache: false,
         forceInMemoryCache: this.params.forceInMemoryCache,
         forceKeepCache: this.params.forceKeepCache,
+        encoding: this.params.encoding,
       });
       const state = await firstWorker.getState();
       if (state.fallback) {
@@ -39,6 +41,7 @@ export default class LSZL {
           worker: this.params.worker,
           noUseCache: false,
           forceKeepCache: true,
+          encoding: this.params.encoding,
         });
         coworker.onFallback = () => this.fallback(coworker);
         workers.push(coworker);
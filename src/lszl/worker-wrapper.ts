@@ -16,6 +16,7 @@ export default class WorkerWrapper {
       noUseCache?: boolean,
       forceInMemoryCache?: boolean,
       forceKeepCache?: boolean,
+      encoding?: string,
     }) {
     const init = createResolver<WorkerState>();
     this.resolvers = {
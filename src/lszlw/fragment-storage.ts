@@ -11,6 +11,8 @@ const GROUP_STORE_NAME = 'group';
 interface FragmentRecord {
   time: number;
   buffer: ArrayBuffer;
+  // ファイル先頭からの位置（記録した場合のみ）
+  offset?: number;
 }
 
 interface GroupRecord {
@@ -54,6 +56,20 @@ export default class FragmentStorage {
   })();
 
   public getFragment = async (name: string, signal?: AbortSignal): Promise<ArrayBuffer | undefined> => {
+    const record = await this.getRecord(name, signal);
+    return record && record.buffer;
+  }
+
+  // 位置を記録せずに保存された断片は無いものとして扱う
+  public getFragmentWithOffset = async (name: string, signal?: AbortSignal): Promise<[ArrayBuffer, number] | undefined> => {
+    const record = await this.getRecord(name, signal);
+    if (!record || typeof record.offset !== 'number') {
+      return undefined;
+    }
+    return [record.buffer, record.offset];
+  }
+
+  private getRecord = async (name: string, signal?: AbortSignal): Promise<FragmentRecord | undefined> => {
     const onabort = signal && signal.onabort;
     const db = await this.prepare.catch(() => { });
     if (!db) {
@@ -75,7 +91,7 @@ export default class FragmentStorage {
 
       promisify(groupStore.put({ time: Date.now() } as GroupRecord, this.params.url)).catch(console.warn);
 
-      return result.buffer;
+      return result;
     } catch (err) {
       if (err && err.name === 'AbortError') {
         return undefined;
@@ -89,7 +105,7 @@ export default class FragmentStorage {
     }
   }
 
-  public putFragment = async (name: string, buffer: ArrayBuffer): Promise<void> => {
+  public putFragment = async (name: string, buffer: ArrayBuffer, offset?: number): Promise<void> => {
     const db = await this.prepare.catch(() => { });
     if (!db) {
       return;
@@ -102,6 +118,7 @@ export default class FragmentStorage {
       const key = getFragmentKey(this.params.url, name);
       const fragmentPromise = promisify(fragmentStore.put({
         buffer,
+        offset,
         time: Date.now(),
       } as FragmentRecord, key));
       const groupPromise = promisify(groupStore.put({ time: Date.now() } as GroupRecord, this.params.url));
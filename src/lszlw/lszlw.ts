@@ -33,13 +33,14 @@ onmessage = (ev: MessageEvent) => {
   const { type, meta } = message;
 
   if (type === MessageType.INIT) {
-    const { payload: { url, forceInMemoryCache, forceKeepCache, noUseCache } } = message as InitRequestMessage;
+    const { payload: { url, forceInMemoryCache, forceKeepCache, noUseCache, encoding } } = message as InitRequestMessage;
     prepare.attachPromise((async () => {
       const lsuzrw = new LSZRWrapper({
         url,
         noUseCache,
         forceInMemoryCache,
         forceKeepCache,
+        encoding,
         onUpdateState: (state) => {
           postMessage({ type: MessageType.UPDATE_STATE, state, meta }, undefined);
         },
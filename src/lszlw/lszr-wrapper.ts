@@ -25,6 +25,8 @@ export default class LSZRWrapper {
       noUseCache?: boolean,
       forceKeepCache?: boolean,
       forceInMemoryCache?: boolean,
+      // UTF-8 フラグのないファイル名のエンコーディング（省略時は CP437、"auto" で推定）
+      encoding?: string,
       onUpdateState: (state: WorkerState) => void;
     }) {
     this.state = {
@@ -47,9 +49,11 @@ export default class LSZRWrapper {
     const promise = (async () => {
       // WASM初期化を待つ
       await wasmReady;
-      const eocdCacheData = this.storage && await this.storage.getFragment(EOCD_ENTRY_NAME);
+      // EOCD はファイル上の位置と一緒に保存し、先頭に余分なデータが付いたアーカイブでも位置を補正できるようにする
+      const eocdCacheData = this.storage && await this.storage.getFragmentWithOffset(EOCD_ENTRY_NAME);
       const cdCacheData = this.storage && await this.storage.getFragment(CD_ENTRY_NAME);
-      let eocdData = eocdCacheData;
+      let eocdData = eocdCacheData && eocdCacheData[0];
+      let eocdOffset = eocdCacheData && eocdCacheData[1];
       let cdData = cdCacheData;
       let lastChunk: DataChunk;
       let inMemoryCache: ArrayBuffer;
@@ -62,17 +66,19 @@ export default class LSZRWrapper {
           }
           lastChunk = await downloadRange(this.params.url, 'bytes=-65557');
           eocdData = lastChunk[0];
+          eocdOffset = lastChunk[1];
         } catch (err) {
           if (!(err instanceof RangeNotSupportedError)) {
             throw err;
           }
           inMemoryCache = await this.cacheInMemory();
-          const start = inMemoryCache.byteLength - 65557;
-          eocdData = inMemoryCache.slice(inMemoryCache.byteLength - 65557);
+          const start = Math.max(0, inMemoryCache.byteLength - 65557);
+          eocdData = inMemoryCache.slice(start);
+          eocdOffset = start;
           lastChunk = [eocdData, start];
         }
       }
-      const uzr = new LSZR(new Uint8Array(eocdData));
+      const uzr = new LSZR(new Uint8Array(eocdData), this.params.encoding, eocdOffset);
 
       if (!eocdCacheData) {
         const eocdRange = uzr.eocdRange;
@@ -83,7 +89,7 @@ export default class LSZRWrapper {
 
         eocdData = lastChunk[0].slice(start, end);
         if (this.storage) {
-          await this.storage.putFragment(EOCD_ENTRY_NAME, eocdData).catch(console.warn);
+          await this.storage.putFragment(EOCD_ENTRY_NAME, eocdData, lastChunk[1] + start).catch(console.warn);
         }
       }
 
@@ -34,6 +34,7 @@ export type InitRequestMessage = RequestMessage<MessageType.INIT, {
   noUseCache?: boolean,
   forceInMemoryCache?: boolean,
   forceKeepCache?: boolean,
+  encoding?: string,
 }>;
 export type InitResponseMessage = RequestMessage<MessageType.INIT, WorkerState>;
 
@@ -0,0 +1,323 @@
+use encoding_rs::{Encoding, BIG5, EUC_KR, GBK, IBM866, SHIFT_JIS, UTF_8, WINDOWS_1251};
+
+// CP437 の 0x80-0xFF に対応する文字
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+// UTF-8 フラグが立っていない名前やコメントを解釈するエンコーディング。
+// APPNOTE では CP437 と定められているが、実際には作成環境のコードページが使われることが多い
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LegacyEncoding {
+    #[default]
+    Cp437,
+    EncodingRs(&'static Encoding),
+}
+
+impl LegacyEncoding {
+    // "cp437"、"gbk"、"shift_jis" などのラベルから求める。ラベルは WHATWG Encoding Standard に従う
+    pub fn for_label(label: &str) -> Option<LegacyEncoding> {
+        let label = label.trim().to_ascii_lowercase();
+        match label.as_str() {
+            "cp437" | "ibm437" | "437" | "csibm437" => Some(LegacyEncoding::Cp437),
+            _ => Encoding::for_label(label.as_bytes()).map(LegacyEncoding::EncodingRs),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LegacyEncoding::Cp437 => "IBM437",
+            LegacyEncoding::EncodingRs(encoding) => encoding.name(),
+        }
+    }
+
+    // 変換できないバイト列を含む場合は None を返す
+    pub fn decode(&self, buf: &[u8]) -> Option<String> {
+        match self {
+            LegacyEncoding::Cp437 => Some(decode_cp437(buf)),
+            LegacyEncoding::EncodingRs(encoding) => encoding
+                .decode_without_bom_handling_and_without_replacement(buf)
+                .map(|s| s.into_owned()),
+        }
+    }
+
+    // 変換できないバイト列は U+FFFD に置き換える
+    pub fn decode_lossy(&self, buf: &[u8]) -> String {
+        match self {
+            LegacyEncoding::Cp437 => decode_cp437(buf),
+            LegacyEncoding::EncodingRs(encoding) => {
+                let (res, _errors) = encoding.decode_without_bom_handling(buf);
+                res.into_owned()
+            }
+        }
+    }
+}
+
+// 自動判定で試すエンコーディング。得点が同じ場合は先に並んでいるものを選ぶ
+fn candidates() -> [LegacyEncoding; 7] {
+    [
+        LegacyEncoding::Cp437,
+        LegacyEncoding::EncodingRs(SHIFT_JIS),
+        LegacyEncoding::EncodingRs(GBK),
+        LegacyEncoding::EncodingRs(BIG5),
+        LegacyEncoding::EncodingRs(EUC_KR),
+        LegacyEncoding::EncodingRs(WINDOWS_1251),
+        LegacyEncoding::EncodingRs(IBM866),
+    ]
+}
+
+// UTF-8 フラグのない名前の集まりから、アーカイブ全体で一貫したエンコーディングを推定する。
+// ASCII だけの名前はどのエンコーディングでも同じなので判定に使わない
+pub fn detect(names: &[&[u8]]) -> LegacyEncoding {
+    let names: Vec<&[u8]> = names.iter().copied().filter(|name| !name.is_ascii()).collect();
+    if names.is_empty() {
+        return LegacyEncoding::default();
+    }
+    // フラグを立てずに UTF-8 で格納するツールも多い。偶然 UTF-8 として正しく読める可能性は低いので優先する
+    if names.iter().all(|name| std::str::from_utf8(name).is_ok()) {
+        return LegacyEncoding::EncodingRs(UTF_8);
+    }
+
+    let mut best = LegacyEncoding::default();
+    let mut best_score = i64::MIN;
+    for candidate in candidates() {
+        if let Some(score) = candidate.score(&names) {
+            if score > best_score {
+                best = candidate;
+                best_score = score;
+            }
+        }
+    }
+    best
+}
+
+// エンコーディングごとに名前に現れると想定される文字の種類
+#[derive(Clone, Copy)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Japanese,
+    SimplifiedChinese,
+    TraditionalChinese,
+    Korean,
+}
+
+#[derive(Clone, Copy)]
+enum CharClass {
+    Latin,
+    Russian,
+    Cyrillic,
+    Kana,
+    HalfwidthKana,
+    Ideograph,
+    Hangul,
+    CjkSymbol,
+    Other,
+}
+
+impl CharClass {
+    fn of(c: char) -> CharClass {
+        match c {
+            '\u{3040}'..='\u{30FF}' => CharClass::Kana,
+            '\u{FF61}'..='\u{FF9F}' => CharClass::HalfwidthKana,
+            '\u{4E00}'..='\u{9FFF}' => CharClass::Ideograph,
+            '\u{AC00}'..='\u{D7A3}' => CharClass::Hangul,
+            '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF5E}' => CharClass::CjkSymbol,
+            'А'..='я' | 'Ё' | 'ё' => CharClass::Russian,
+            '\u{0400}'..='\u{04FF}' => CharClass::Cyrillic,
+            '\u{00C0}'..='\u{024F}' if c.is_alphabetic() => CharClass::Latin,
+            _ => CharClass::Other,
+        }
+    }
+}
+
+impl LegacyEncoding {
+    fn script(&self) -> Script {
+        match self.name() {
+            "Shift_JIS" | "EUC-JP" | "ISO-2022-JP" => Script::Japanese,
+            "GBK" | "gb18030" => Script::SimplifiedChinese,
+            "Big5" => Script::TraditionalChinese,
+            "EUC-KR" => Script::Korean,
+            "windows-1251" | "IBM866" | "KOI8-R" | "KOI8-U" | "ISO-8859-5" => Script::Cyrillic,
+            _ => Script::Latin,
+        }
+    }
+
+    // すべての名前を変換できた場合だけ、文字の種類ごとのもっともらしさを合計した得点を返す
+    fn score(&self, names: &[&[u8]]) -> Option<i64> {
+        let mut total = 0;
+        for name in names {
+            let decoded = self.decode(name)?;
+            let mut prev: Option<char> = None;
+            for c in decoded.chars() {
+                if !c.is_ascii() {
+                    total += self.char_score(c);
+                }
+                // 単語の途中で小文字から大文字に変わるのは 1 バイト系で読み違えたときの典型
+                if let Some(p) = prev {
+                    if p.is_lowercase() && c.is_uppercase() && !(p.is_ascii() && c.is_ascii()) {
+                        total -= 4;
+                    }
+                }
+                prev = Some(c);
+            }
+        }
+        Some(total)
+    }
+
+    fn char_score(&self, c: char) -> i64 {
+        match (self.script(), CharClass::of(c)) {
+            (Script::Latin, CharClass::Latin) => 2,
+            (Script::Cyrillic, CharClass::Russian) => 2,
+            (Script::Cyrillic, CharClass::Cyrillic) => 0,
+            (Script::Japanese, CharClass::Kana) => 4,
+            (Script::Japanese, CharClass::HalfwidthKana) => -1,
+            (Script::Korean, CharClass::Hangul) => {
+                if self.is_common(c) {
+                    4
+                } else {
+                    1
+                }
+            }
+            // 韓国語のファイル名に漢字が使われることはまれ
+            (Script::Korean, CharClass::Ideograph) => -2,
+            (Script::Japanese | Script::SimplifiedChinese | Script::TraditionalChinese, CharClass::Ideograph) => {
+                if self.is_common(c) {
+                    3
+                } else {
+                    1
+                }
+            }
+            (Script::SimplifiedChinese | Script::TraditionalChinese | Script::Korean, CharClass::Kana) => -2,
+            (
+                Script::Japanese | Script::SimplifiedChinese | Script::TraditionalChinese | Script::Korean,
+                CharClass::CjkSymbol,
+            ) => 2,
+            _ => -4,
+        }
+    }
+
+    // 2 バイト文字が第 1 水準などの頻出する範囲に割り当てられているか。
+    // 他のエンコーディングのバイト列を読み違えると、まれな文字ばかりになることを利用する
+    fn is_common(&self, c: char) -> bool {
+        let encoding = match self {
+            LegacyEncoding::EncodingRs(encoding) => encoding,
+            LegacyEncoding::Cp437 => return false,
+        };
+        let mut buf = [0; 4];
+        let (bytes, _, _) = encoding.encode(c.encode_utf8(&mut buf));
+        let (lead, trail) = match *bytes {
+            [lead, trail] => (lead, trail),
+            _ => return false,
+        };
+        match self.script() {
+            Script::Japanese => (0x88..=0x98).contains(&lead),
+            Script::SimplifiedChinese => (0xB0..=0xD7).contains(&lead) && trail >= 0xA1,
+            Script::TraditionalChinese => (0xA4..=0xC6).contains(&lead),
+            Script::Korean => (0xB0..=0xC8).contains(&lead) && trail >= 0xA1,
+            Script::Latin | Script::Cyrillic => false,
+        }
+    }
+}
+
+fn decode_cp437(buf: &[u8]) -> String {
+    buf.iter()
+        .map(|&b| if b < 0x80 { b as char } else { CP437_HIGH[(b - 0x80) as usize] })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_label() {
+        assert_eq!(LegacyEncoding::for_label("CP437"), Some(LegacyEncoding::Cp437));
+        assert_eq!(LegacyEncoding::for_label("gbk").unwrap().name(), "GBK");
+        assert_eq!(LegacyEncoding::for_label("shift_jis").unwrap().name(), "Shift_JIS");
+        assert_eq!(LegacyEncoding::for_label("cp866").unwrap().name(), "IBM866");
+        assert_eq!(LegacyEncoding::for_label("unknown"), None);
+    }
+
+    #[test]
+    fn test_decode_cp437() {
+        // "Größe.txt" を CP437 で表したもの
+        let name = [0x47, 0x72, 0x94, 0xE1, 0x65, 0x2E, 0x74, 0x78, 0x74];
+        assert_eq!(LegacyEncoding::Cp437.decode(&name).unwrap(), "Größe.txt");
+    }
+
+    #[test]
+    fn test_decode_legacy_encodings() {
+        let cases: [(&str, &[u8], &str); 4] = [
+            ("gbk", &[0xD6, 0xD0, 0xCE, 0xC4], "中文"),
+            ("euc-kr", &[0xC7, 0xD1, 0xB1, 0xDB], "한글"),
+            ("windows-1251", &[0xD4, 0xE0, 0xE9, 0xEB], "Файл"),
+            ("big5", &[0xA4, 0xA4, 0xA4, 0xE5], "中文"),
+        ];
+        for (label, bytes, expected) in cases {
+            let encoding = LegacyEncoding::for_label(label).unwrap();
+            assert_eq!(encoding.decode(bytes).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid_sequence() {
+        let encoding = LegacyEncoding::for_label("shift_jis").unwrap();
+        assert_eq!(encoding.decode(&[0x82]), None);
+        assert_eq!(encoding.decode_lossy(&[0x82]), "\u{FFFD}");
+    }
+
+    // Helper: 名前を指定したエンコーディングで符号化する
+    fn encode_names(label: &str, names: &[&str]) -> Vec<Vec<u8>> {
+        let encoding = Encoding::for_label(label.as_bytes()).unwrap();
+        names.iter().map(|name| encoding.encode(name).0.into_owned()).collect()
+    }
+
+    fn detect_names(names: &[Vec<u8>]) -> &'static str {
+        let names: Vec<&[u8]> = names.iter().map(|n| n.as_slice()).collect();
+        detect(&names).name()
+    }
+
+    #[test]
+    fn test_detect_legacy_encodings() {
+        let cases: [(&str, &[&str], &str); 7] = [
+            ("shift_jis", &["第1巻/", "第1巻/表紙.jpg", "第1巻/あとがき.txt"], "Shift_JIS"),
+            ("shift_jis", &["漢字.txt"], "Shift_JIS"),
+            ("gbk", &["新建文件夹/", "新建文件夹/图片01.jpg", "说明.txt"], "GBK"),
+            ("big5", &["新增資料夾/", "新增資料夾/圖片01.jpg", "說明.txt"], "Big5"),
+            ("euc-kr", &["새 폴더/", "새 폴더/사진01.jpg", "설명.txt"], "EUC-KR"),
+            ("windows-1251", &["Новая папка/", "Новая папка/Фото01.jpg", "Описание.txt"], "windows-1251"),
+            ("ibm866", &["Новая папка/", "Новая папка/Фото01.jpg", "Описание.txt"], "IBM866"),
+        ];
+        for (label, names, expected) in cases {
+            assert_eq!(detect_names(&encode_names(label, names)), expected, "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn test_detect_cp437() {
+        // "Größe.txt", "Café/menü.txt"
+        let names = vec![
+            vec![0x47, 0x72, 0x94, 0xE1, 0x65, 0x2E, 0x74, 0x78, 0x74],
+            vec![0x43, 0x61, 0x66, 0x82, 0x2F, 0x6D, 0x65, 0x6E, 0x81, 0x2E, 0x74, 0x78, 0x74],
+        ];
+        assert_eq!(detect_names(&names), "IBM437");
+    }
+
+    #[test]
+    fn test_detect_ascii_and_utf8() {
+        // ASCII だけなら既定の CP437
+        assert_eq!(detect(&[b"a.txt", b"b/c.txt"]), LegacyEncoding::Cp437);
+        assert_eq!(detect(&[]), LegacyEncoding::Cp437);
+        // フラグのない UTF-8
+        let names = ["テスト.txt".as_bytes(), b"a.txt"];
+        assert_eq!(detect(&names).name(), "UTF-8");
+    }
+}
\ No newline at end of file
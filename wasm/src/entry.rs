@@ -0,0 +1,153 @@
+use crate::encoding::LegacyEncoding;
+use crate::zip;
+use wasm_bindgen::prelude::*;
+
+// JS 側に公開するエントリのメタデータ
+#[wasm_bindgen]
+pub struct EntryInfo {
+    index: usize,
+    name: String,
+    raw_name: Vec<u8>,
+    is_name_lossy: bool,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    compression_method: u16,
+    crc32: u32,
+    last_modified: Option<i64>,
+    last_accessed: Option<i64>,
+    created: Option<i64>,
+    is_encrypted: bool,
+    is_directory: bool,
+    is_symlink: bool,
+    unix_mode: Option<u32>,
+    host_os: u8,
+    comment: String,
+    raw_comment: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl EntryInfo {
+    // CD 上の順番。名前の代わりに LSZR の各メソッドに渡せる
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // 変換前の名前のバイト列
+    #[wasm_bindgen(getter, js_name = rawName)]
+    pub fn raw_name(&self) -> Vec<u8> {
+        self.raw_name.clone()
+    }
+
+    // name に変換できない文字が含まれ、U+FFFD に置き換えられている
+    #[wasm_bindgen(getter, js_name = isNameLossy)]
+    pub fn is_name_lossy(&self) -> bool {
+        self.is_name_lossy
+    }
+
+    #[wasm_bindgen(getter, js_name = compressedSize)]
+    pub fn compressed_size(&self) -> f64 {
+        self.compressed_size as f64
+    }
+
+    #[wasm_bindgen(getter, js_name = uncompressedSize)]
+    pub fn uncompressed_size(&self) -> f64 {
+        self.uncompressed_size as f64
+    }
+
+    #[wasm_bindgen(getter, js_name = compressionMethod)]
+    pub fn compression_method(&self) -> u16 {
+        self.compression_method
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    // 日時は Unix エポックからのミリ秒。JS の Date にそのまま渡せる。記録されていなければ undefined
+    #[wasm_bindgen(getter, js_name = lastModified)]
+    pub fn last_modified(&self) -> Option<f64> {
+        self.last_modified.map(|t| t as f64)
+    }
+
+    #[wasm_bindgen(getter, js_name = lastAccessed)]
+    pub fn last_accessed(&self) -> Option<f64> {
+        self.last_accessed.map(|t| t as f64)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn created(&self) -> Option<f64> {
+        self.created.map(|t| t as f64)
+    }
+
+    #[wasm_bindgen(getter, js_name = isEncrypted)]
+    pub fn is_encrypted(&self) -> bool {
+        self.is_encrypted
+    }
+
+    #[wasm_bindgen(getter, js_name = isDirectory)]
+    pub fn is_directory(&self) -> bool {
+        self.is_directory
+    }
+
+    #[wasm_bindgen(getter, js_name = isSymlink)]
+    pub fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    // Unix 系で作成されたエントリの st_mode（ファイル種別とパーミッション）
+    #[wasm_bindgen(getter, js_name = unixMode)]
+    pub fn unix_mode(&self) -> Option<u32> {
+        self.unix_mode
+    }
+
+    // 作成した OS。0: MS-DOS, 3: Unix, 10: NTFS, 19: OS X など
+    #[wasm_bindgen(getter, js_name = hostOs)]
+    pub fn host_os(&self) -> u8 {
+        self.host_os
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn comment(&self) -> String {
+        self.comment.clone()
+    }
+
+    // 変換前のコメントのバイト列
+    #[wasm_bindgen(getter, js_name = rawComment)]
+    pub fn raw_comment(&self) -> Vec<u8> {
+        self.raw_comment.clone()
+    }
+}
+
+impl EntryInfo {
+    // コメントはファイル名と同じエンコーディングで変換する
+    pub(crate) fn new(cdh: &zip::CDHeader, index: usize, encoding: LegacyEncoding) -> Self {
+        let timestamps = cdh.timestamps();
+        EntryInfo {
+            index,
+            name: cdh.file_name.clone(),
+            raw_name: cdh.file_name_bytes.clone(),
+            is_name_lossy: cdh.is_name_lossy,
+            compressed_size: cdh.compressed_size,
+            uncompressed_size: cdh.uncompressed_size,
+            compression_method: cdh.compression_method,
+            crc32: cdh.crc32,
+            last_modified: timestamps.modified,
+            last_accessed: timestamps.accessed,
+            created: timestamps.created,
+            is_encrypted: cdh.is_encrypted,
+            is_directory: cdh.is_dir(),
+            is_symlink: cdh.is_symlink(),
+            unix_mode: cdh.unix_mode(),
+            host_os: cdh.host_os(),
+            comment: cdh.decoded_comment(encoding),
+            raw_comment: cdh.file_comment.clone(),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::zip::CDHeader;
+use std::collections::HashMap;
+
+// parse_cd の結果から作る検索用の索引
+pub struct EntryIndex {
+    by_name: HashMap<String, usize>,
+    // 変換に失敗した名前でも引けるよう、生のバイト列からも引けるようにする
+    by_raw_name: HashMap<Vec<u8>, usize>,
+    // ローカルヘッダのオフセット順に並べたエントリの (offset, index)
+    by_offset: Vec<(u64, usize)>,
+}
+
+impl EntryIndex {
+    pub fn new(entries: &[CDHeader]) -> EntryIndex {
+        let mut by_name = HashMap::with_capacity(entries.len());
+        let mut by_raw_name = HashMap::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            // 同名のエントリがある場合は先に現れたものを優先する
+            by_name.entry(entry.file_name.clone()).or_insert(i);
+            by_raw_name.entry(entry.file_name_bytes.clone()).or_insert(i);
+        }
+        let mut by_offset: Vec<(u64, usize)> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.relative_offset_of_local_header, i))
+            .collect();
+        by_offset.sort_unstable();
+        EntryIndex {
+            by_name,
+            by_raw_name,
+            by_offset,
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn find_raw(&self, raw_name: &[u8]) -> Option<usize> {
+        self.by_raw_name.get(raw_name).copied()
+    }
+
+    // offset より後ろにある最初のローカルヘッダのオフセットを返す
+    pub fn next_offset(&self, offset: u64) -> Option<u64> {
+        let i = self.by_offset.partition_point(|&(o, _)| o <= offset);
+        self.by_offset.get(i).map(|&(o, _)| o)
+    }
+}
+
+impl Default for EntryIndex {
+    fn default() -> Self {
+        EntryIndex::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::LegacyEncoding;
+    use crate::zip::tests::create_cd_header;
+    use crate::zip::{parse_cd, COMPRESSION_METHOD_STORED};
+    use std::io;
+
+    #[test]
+    fn test_find_and_next_offset() {
+        let mut cd_data = Vec::new();
+        // オフセット順ではない並び
+        cd_data.extend(create_cd_header("b.txt", 200, 10, 10, 0, COMPRESSION_METHOD_STORED, true));
+        cd_data.extend(create_cd_header("a.txt", 0, 10, 10, 0, COMPRESSION_METHOD_STORED, true));
+        cd_data.extend(create_cd_header("c.txt", 100, 10, 10, 0, COMPRESSION_METHOD_STORED, true));
+        let entries = parse_cd(&mut io::Cursor::new(cd_data), 3, Some(LegacyEncoding::default())).unwrap().0;
+
+        let index = EntryIndex::new(&entries);
+        assert_eq!(index.find("a.txt"), Some(1));
+        assert_eq!(index.find("d.txt"), None);
+        assert_eq!(index.find_raw(b"c.txt"), Some(2));
+        assert_eq!(index.next_offset(0), Some(100));
+        assert_eq!(index.next_offset(100), Some(200));
+        assert_eq!(index.next_offset(200), None);
+    }
+
+    #[test]
+    fn test_index_100k_entries() {
+        const COUNT: u64 = 100_000;
+        let mut cd_data = Vec::new();
+        // 後ろのエントリから順に並べ、オフセット順と CD 順が逆になるようにする
+        for i in (0..COUNT).rev() {
+            let name = format!("pages/{:06}.jpg", i);
+            cd_data.extend(create_cd_header(&name, (i * 1000) as u32, 900, 900, 0, COMPRESSION_METHOD_STORED, true));
+        }
+        let entries = parse_cd(&mut io::Cursor::new(cd_data), COUNT as usize, Some(LegacyEncoding::default())).unwrap().0;
+
+        let index = EntryIndex::new(&entries);
+        for i in 0..COUNT {
+            let name = format!("pages/{:06}.jpg", i);
+            let found = index.find(&name).unwrap();
+            assert_eq!(entries[found].file_name, name);
+            let next = index.next_offset(entries[found].relative_offset_of_local_header);
+            assert_eq!(next, if i + 1 < COUNT { Some((i + 1) * 1000) } else { None });
+        }
+    }
+}
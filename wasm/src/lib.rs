@@ -1,120 +1,503 @@
 #[macro_use]
 mod utils;
+mod encoding;
+mod entry;
+mod index;
+mod multipart;
+mod planner;
+mod shared;
+mod snapshot;
+mod stream;
+mod winzip_aes;
 mod zip;
+mod zipcrypto;
 
-use js_sys::{Array, Error};
+pub use entry::EntryInfo;
+pub use multipart::ByteRangeSegment;
+pub use planner::{RangePlan, RangeSlice};
+
+use js_sys::{Array, Error, Uint8Array};
 use wasm_bindgen::prelude::*;
+use std::borrow::Cow;
 use std::io::Cursor;
 use std::cmp;
 
+#[wasm_bindgen(typescript_custom_section)]
+const ENTRY_KEY: &'static str = r#"
+// エントリの指定方法。名前のほか、CD 上の順番や名前の生のバイト列でも指定できる
+export type EntryKey = string | number | Uint8Array;
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "EntryKey")]
+    pub type EntryKey;
+}
+
 #[wasm_bindgen]
 pub struct LSZR {
     eocd: zip::EOCD,
-    entries: Vec<zip::CDHeader>,
+    // 先頭に付いた余分なデータの長さ。CD やローカルヘッダのオフセットに足すと実際の位置になる
+    base_offset: u64,
+    directory: Directory,
+    requested_encoding: Option<encoding::LegacyEncoding>,
+    encoding: encoding::LegacyEncoding,
+}
+
+// 解析したエントリの持ち方。共有インデックスに接続した場合は、必要なエントリだけをその都度バッファから読む
+enum Directory {
+    Owned {
+        entries: Vec<zip::CDHeader>,
+        index: index::EntryIndex,
+    },
+    Shared(shared::SharedIndex<Uint8Array>),
+}
+
+impl Directory {
+    fn new(entries: Vec<zip::CDHeader>) -> Directory {
+        let index = index::EntryIndex::new(&entries);
+        Directory::Owned { entries, index }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Directory::Owned { entries, .. } => entries.len(),
+            Directory::Shared(shared) => shared.entry_count(),
+        }
+    }
+
+    fn entry(&self, i: usize) -> Result<Cow<'_, zip::CDHeader>, JsValue> {
+        match self {
+            Directory::Owned { entries, .. } => Ok(Cow::Borrowed(&entries[i])),
+            Directory::Shared(shared) => Ok(Cow::Owned(shared.entry(i)?)),
+        }
+    }
+
+    fn find(&self, name: &str) -> Result<Option<usize>, JsValue> {
+        match self {
+            Directory::Owned { index, .. } => Ok(index.find(name)),
+            Directory::Shared(shared) => Ok(shared.find(name)?),
+        }
+    }
+
+    fn find_raw(&self, raw_name: &[u8]) -> Result<Option<usize>, JsValue> {
+        match self {
+            Directory::Owned { index, .. } => Ok(index.find_raw(raw_name)),
+            Directory::Shared(shared) => Ok(shared.find_raw(raw_name)?),
+        }
+    }
+
+    fn next_offset(&self, offset: u64) -> Option<u64> {
+        match self {
+            Directory::Owned { index, .. } => index.next_offset(offset),
+            Directory::Shared(shared) => shared.next_offset(offset),
+        }
+    }
 }
 
 #[wasm_bindgen]
 #[derive(Copy, Clone)]
 pub struct Range {
-    #[wasm_bindgen(readonly, js_name=offset)]
-    pub offset: u32,
-    #[wasm_bindgen(readonly, js_name=size)]
-    pub size: u32,
+    offset: u64,
+    size: u64,
+}
+
+// JS 側では BigInt を避けるため Number として返す（2^53 までは正確に表現できる）
+#[wasm_bindgen]
+impl Range {
+    #[wasm_bindgen(getter, js_name=offset)]
+    pub fn offset(&self) -> f64 {
+        self.offset as f64
+    }
+
+    #[wasm_bindgen(getter, js_name=size)]
+    pub fn size(&self) -> f64 {
+        self.size as f64
+    }
+}
+
+// 圧縮データをチャンク単位で受け取り、展開したデータをチャンク単位で返す
+#[wasm_bindgen]
+pub struct EntryStream {
+    decoder: Option<stream::StreamDecoder>,
+}
+
+#[wasm_bindgen]
+impl EntryStream {
+    // ローカルヘッダから始まるデータの続きを渡す。展開できた分を返す（空の場合もある）
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JsValue> {
+        match &mut self.decoder {
+            Some(decoder) => Ok(decoder.push(chunk)?),
+            None => Err(JsValue::from(Error::new("Stream already finished."))),
+        }
+    }
+
+    // 全データを渡し終えたら呼ぶ。残りの展開データを返し、CRC を検証する
+    pub fn finish(&mut self) -> Result<Vec<u8>, JsValue> {
+        match self.decoder.take() {
+            Some(decoder) => Ok(decoder.finish()?),
+            None => Err(JsValue::from(Error::new("Stream already finished."))),
+        }
+    }
 }
 
 #[wasm_bindgen]
 impl LSZR {
-    #[wasm_bindgen(constructor, catch)]
-    pub fn new(data: Vec<u8>) -> Result<LSZR, JsValue> {
+    // encoding は UTF-8 フラグのないファイル名に使うエンコーディングのラベル（省略時は CP437）。
+    // "auto" を指定すると parseCD でセントラルディレクトリ全体から推定する。
+    // data_offset は data のファイル先頭からの位置。末尾だけを渡す場合に指定すると、
+    // 先頭に別のデータが付いたアーカイブ（自己解凍形式など）でも正しい範囲を返せる
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: Vec<u8>, encoding: Option<String>, data_offset: Option<f64>) -> Result<LSZR, JsValue> {
+        let requested_encoding = match encoding.as_deref() {
+            Some("auto") => None,
+            Some(label) => match encoding::LegacyEncoding::for_label(label) {
+                Some(encoding) => Some(encoding),
+                None => {
+                    let message = format!("Unknown encoding: {}", label);
+                    return Err(JsValue::from(Error::new(message.as_str())));
+                }
+            },
+            None => Some(encoding::LegacyEncoding::default()),
+        };
         let len = data.len();
         if len < 22 {
             return Err(JsValue::from(Error::new("Data length invalid.")));
         }
         let mut reader = Cursor::new(data);
-        let eocd = zip::parse_eocd(&mut reader)?;
+        let eocd = zip::parse_eocd(&mut reader, data_offset.map(|offset| offset as u64))?;
 
         if eocd.number_of_this_disk != 0 || eocd.number_of_disk_start_eocd != 0 {
             return Err(JsValue::from(Error::new("Disk split is not supported.")));
         }
 
-        if eocd.number_of_this_disk == 0xFFFF {
-            return Err(JsValue::from(Error::new("ZIP64 is not supported.")));
-        }
-        
+        let base_offset = eocd.base_offset(data_offset.unwrap_or(0.0) as u64);
+
         let result = Self {
-            eocd: eocd,
-            entries: vec![]
+            eocd,
+            base_offset,
+            directory: Directory::new(vec![]),
+            requested_encoding,
+            encoding: requested_encoding.unwrap_or_default(),
         };
 
         Result::Ok(result)
     }
 
-    #[wasm_bindgen(catch, js_name = parseCD)]
+    // serializeIndex で保存したインデックスから復元する。parseCD を呼ばずにそのままエントリを読める
+    #[wasm_bindgen(js_name = fromIndex)]
+    pub fn from_index(data: &[u8]) -> Result<LSZR, JsValue> {
+        let snapshot = snapshot::deserialize(data)?;
+        Result::Ok(Self {
+            eocd: snapshot.eocd,
+            base_offset: snapshot.base_offset,
+            directory: Directory::new(snapshot.entries),
+            requested_encoding: snapshot.requested_encoding,
+            encoding: snapshot.encoding,
+        })
+    }
+
+    // 解析済みの EOCD と CD をバイナリ形式で保存する。ワーカー間での受け渡しやキャッシュに使う
+    #[wasm_bindgen(js_name = serializeIndex)]
+    pub fn serialize_index(&self) -> Result<Vec<u8>, JsValue> {
+        let entries = self.all_entries()?;
+        Ok(snapshot::serialize(&self.eocd, self.base_offset, &entries, self.requested_encoding, self.encoding))
+    }
+
+    // 解析済みのインデックスを、他のワーカーの LSZR が attachSharedIndex でそのまま使える形式で返す。
+    // SharedArrayBuffer を使える環境ではその上に作るので、postMessage で渡してもコピーされない
+    #[wasm_bindgen(js_name = exportSharedIndex)]
+    pub fn export_shared_index(&self) -> Result<Uint8Array, JsValue> {
+        if let Directory::Shared(shared) = &self.directory {
+            return Ok(shared.buffer().clone());
+        }
+        let entries = self.all_entries()?;
+        let data = shared::build(&self.eocd, self.base_offset, &entries, self.requested_encoding, self.encoding);
+        let has_shared_array_buffer = js_sys::Reflect::has(&js_sys::global(), &JsValue::from("SharedArrayBuffer"))?;
+        let buffer = if has_shared_array_buffer {
+            let buffer = Uint8Array::new(&js_sys::SharedArrayBuffer::new(data.len() as u32));
+            buffer.copy_from(&data);
+            buffer
+        } else {
+            Uint8Array::from(&data[..])
+        };
+        Ok(buffer)
+    }
+
+    // exportSharedIndex で作ったバッファに読み取り専用で接続する。
+    // バッファはコピーせず、エントリは必要になったときにバッファから読む
+    #[wasm_bindgen(js_name = attachSharedIndex)]
+    pub fn attach_shared_index(buffer: Uint8Array) -> Result<LSZR, JsValue> {
+        let shared = shared::SharedIndex::attach(buffer)?;
+        Result::Ok(Self {
+            eocd: shared.eocd().clone(),
+            base_offset: shared.base_offset(),
+            requested_encoding: shared.requested_encoding(),
+            encoding: shared.encoding(),
+            directory: Directory::Shared(shared),
+        })
+    }
+
+    #[wasm_bindgen(js_name = parseCD)]
     pub fn parse_cd(&mut self, data: Vec<u8>) -> Result<Array, JsValue> {
         let mut reader = Cursor::new(data);
-        self.entries = zip::parse_cd(
+        let (entries, encoding) = zip::parse_cd(
             &mut reader,
             self.eocd.total_number_of_entries_in_cd as usize,
+            self.requested_encoding,
         )?;
-
         let names = Array::new();
-        for e in &self.entries {
+        for e in &entries {
             let name = JsValue::from(e.file_name.clone());
             names.push(&name);
         }
+        self.encoding = encoding;
+        self.directory = Directory::new(entries);
         Result::Ok(names)
     }
 
-    #[wasm_bindgen(catch, js_name = getRange)]
-    pub fn get_range(&mut self, name: String) -> Result<Range, JsValue> {
-        for entry in &self.entries {
-            if name == entry.file_name {
-                let mut end = self.eocd.cd_offset;
-                for next in &self.entries {
-                    if next.relative_offset_of_local_header <= entry.relative_offset_of_local_header {
-                        continue;
-                    }
-                    end = cmp::min(end, next.relative_offset_of_local_header);
-                }
-                return Result::Ok(Range {
-                    offset: entry.relative_offset_of_local_header,
-                    size: end - entry.relative_offset_of_local_header - 1,
-                });
-            }
+    #[wasm_bindgen(js_name = getRange)]
+    pub fn get_range(&mut self, name: EntryKey) -> Result<Range, JsValue> {
+        let entry = self.find_entry(&name)?;
+        let offset = entry.relative_offset_of_local_header;
+        let end = match self.directory.next_offset(offset) {
+            Some(next) => cmp::min(self.eocd.cd_offset, next),
+            None => self.eocd.cd_offset,
+        };
+        // 次のエントリと同じ位置や CD 以降を指す壊れたオフセットでは範囲が求まらない
+        let size = offset
+            .checked_add(1)
+            .and_then(|start| end.checked_sub(start))
+            .ok_or_else(|| JsValue::from(Error::new("Invalid local header offset.")))?;
+        Result::Ok(Range {
+            offset: self.base_offset + offset,
+            size,
+        })
+    }
+
+    // CD のサイズ情報から求めたエントリの範囲を返す。getRange と異なり size はバイト数そのもの
+    #[wasm_bindgen(js_name = getExactRange)]
+    pub fn get_exact_range(&self, name: EntryKey, extra_field_allowance: Option<u32>) -> Result<Range, JsValue> {
+        let entry = self.find_entry(&name)?;
+        let allowance = extra_field_allowance
+            .map(|n| n as u64)
+            .unwrap_or(zip::DEFAULT_EXTRA_FIELD_ALLOWANCE);
+        let (offset, size) = zip::entry_range(&entry, allowance, self.eocd.cd_offset);
+        Ok(Range {
+            offset: self.base_offset + offset,
+            size,
+        })
+    }
+
+    // getExactRange で取得したデータでローカルヘッダの拡張フィールドが収まらなかった場合に、
+    // 続けて取得すべき残りの範囲を返す。足りていれば undefined
+    #[wasm_bindgen(js_name = getRemainingRange)]
+    pub fn get_remaining_range(&self, name: EntryKey, data: &[u8]) -> Result<Option<Range>, JsValue> {
+        let entry = self.find_entry(&name)?;
+        let length = zip::local_entry_length(data, &entry)?;
+        let received = data.len() as u64;
+        if length <= received {
+            return Ok(None);
         }
-        let message = format!("Entry not found: {}", name);
-        Err(JsValue::from(Error::new(message.as_str())))
+        Ok(Some(Range {
+            offset: self.base_offset + entry.relative_offset_of_local_header + received,
+            size: length - received,
+        }))
+    }
+
+    // 複数のエントリを少ないリクエストで取得するための範囲を求める。
+    // 各エントリは getExactRange と同じ範囲で、隙間が max_gap バイト以下のものを max_range_bytes までまとめる
+    #[wasm_bindgen(js_name = planRanges)]
+    pub fn plan_ranges(&self, names: Vec<EntryKey>, max_gap: f64, max_range_bytes: f64) -> Result<Vec<RangePlan>, JsValue> {
+        let mut spans = Vec::with_capacity(names.len());
+        for name in &names {
+            let entry = self.find_entry(name)?;
+            let (offset, size) = zip::entry_range(&entry, zip::DEFAULT_EXTRA_FIELD_ALLOWANCE, self.eocd.cd_offset);
+            spans.push((self.base_offset + offset, size));
+        }
+        Ok(planner::plan_ranges(&spans, max_gap as u64, max_range_bytes as u64))
     }
 
-    #[wasm_bindgen(catch, js_name = getData)]
-    pub fn get_data(&mut self, name: String, data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
-        let entry = self.find_entry(name)?;
+    #[wasm_bindgen(js_name = getData)]
+    pub fn get_data(&mut self, name: EntryKey, data: Vec<u8>, skip_crc_check: Option<bool>) -> Result<Vec<u8>, JsValue> {
+        let entry = self.find_entry(&name)?;
         let reader = Cursor::new(data);
-        let result = zip::load_file(reader, entry)?;
+        let result = zip::load_file(reader, &entry, !skip_crc_check.unwrap_or(false), None)?;
+        Ok(result)
+    }
 
+    // アーカイブの任意の位置から始まる断片からエントリを読み込む。chunk_offset は chunk のファイル上の位置
+    #[wasm_bindgen(js_name = getDataFromChunk)]
+    pub fn get_data_from_chunk(
+        &self,
+        name: EntryKey,
+        chunk: &[u8],
+        chunk_offset: f64,
+        skip_crc_check: Option<bool>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let entry = self.find_entry(&name)?;
+        let entry_offset = self.base_offset + entry.relative_offset_of_local_header;
+        let result = zip::load_file_from_chunk(
+            chunk,
+            chunk_offset as u64,
+            entry_offset,
+            &entry,
+            !skip_crc_check.unwrap_or(false),
+            None,
+        )?;
+        Ok(result)
+    }
+
+    #[wasm_bindgen(js_name = getDataWithPassword)]
+    pub fn get_data_with_password(
+        &mut self,
+        name: EntryKey,
+        data: Vec<u8>,
+        password: String,
+        skip_crc_check: Option<bool>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let entry = self.find_entry(&name)?;
+        let reader = Cursor::new(data);
+        let result = zip::load_file(
+            reader,
+            &entry,
+            !skip_crc_check.unwrap_or(false),
+            Some(password.as_bytes()),
+        )?;
+        Ok(result)
+    }
+
+    #[wasm_bindgen(js_name = createStream)]
+    pub fn create_stream(&self, name: EntryKey, skip_crc_check: Option<bool>) -> Result<EntryStream, JsValue> {
+        let entry = self.find_entry(&name)?;
         if entry.is_encrypted {
             return Err(JsValue::from(Error::new("encrypted.")));
         }
-        Ok(result)
+        let decoder = stream::StreamDecoder::new(&entry, !skip_crc_check.unwrap_or(false))?;
+        Ok(EntryStream {
+            decoder: Some(decoder),
+        })
+    }
+
+    // シンボリックリンクのエントリのデータ（ローカルヘッダから）を渡すと、リンク先のエントリ名を返す
+    #[wasm_bindgen(js_name = readLink)]
+    pub fn read_link(&self, name: EntryKey, data: Vec<u8>) -> Result<String, JsValue> {
+        let entry = self.find_entry(&name)?;
+        if !entry.is_symlink() {
+            let message = format!("Not a symbolic link: {}", entry.file_name);
+            return Err(JsValue::from(Error::new(message.as_str())));
+        }
+        let target = zip::load_file(Cursor::new(data), &entry, true, None)?;
+        let target = zip::decode_lossy(&target, entry.is_utf8, self.encoding);
+        let resolved = match zip::resolve_symlink_target(&entry.file_name, &target) {
+            Some(resolved) => resolved,
+            None => {
+                let message = format!("Link target is outside the archive: {}", target);
+                return Err(JsValue::from(Error::new(message.as_str())));
+            }
+        };
+        // ディレクトリへのリンクは末尾に '/' が付いたエントリを指す
+        let dir = format!("{}/", resolved);
+        for candidate in [resolved, dir] {
+            if self.directory.find(&candidate)?.is_some() {
+                return Ok(candidate);
+            }
+        }
+        let message = format!("Link target not found: {}", target);
+        Err(JsValue::from(Error::new(message.as_str())))
+    }
+
+    #[wasm_bindgen(js_name = getEntry)]
+    pub fn get_entry(&self, name: EntryKey) -> Result<EntryInfo, JsValue> {
+        let i = self.find_index(&name)?;
+        Ok(EntryInfo::new(&*self.directory.entry(i)?, i, self.encoding))
     }
 
-    fn find_entry(&self, name: String) -> Result<&zip::CDHeader, JsValue> {
-        for entry in &self.entries {
-            if entry.file_name == name {
-                return Result::Ok(entry);
+    pub fn entries(&self) -> Result<Vec<EntryInfo>, JsValue> {
+        let mut entries = Vec::with_capacity(self.directory.len());
+        for i in 0..self.directory.len() {
+            entries.push(EntryInfo::new(&*self.directory.entry(i)?, i, self.encoding));
+        }
+        Ok(entries)
+    }
+
+    fn find_entry(&self, name: &EntryKey) -> Result<Cow<'_, zip::CDHeader>, JsValue> {
+        let i = self.find_index(name)?;
+        self.directory.entry(i)
+    }
+
+    fn all_entries(&self) -> Result<Cow<'_, [zip::CDHeader]>, JsValue> {
+        match &self.directory {
+            Directory::Owned { entries, .. } => Ok(Cow::Borrowed(entries)),
+            Directory::Shared(shared) => {
+                let mut entries = Vec::with_capacity(shared.entry_count());
+                for i in 0..shared.entry_count() {
+                    entries.push(shared.entry(i)?);
+                }
+                Ok(Cow::Owned(entries))
+            }
+        }
+    }
+
+    // 変換できなかった名前や重複した名前のエントリは、順番か生のバイト列で指定する
+    fn find_index(&self, name: &EntryKey) -> Result<usize, JsValue> {
+        let found = if let Some(name) = name.as_string() {
+            self.directory.find(&name)?
+        } else if let Some(i) = name.as_f64() {
+            if i >= 0.0 && i.fract() == 0.0 && (i as usize) < self.directory.len() {
+                Some(i as usize)
+            } else {
+                None
+            }
+        } else if let Some(raw_name) = name.dyn_ref::<Uint8Array>() {
+            self.directory.find_raw(&raw_name.to_vec())?
+        } else {
+            None
+        };
+        match found {
+            Some(i) => Result::Ok(i),
+            None => {
+                let message = match (name.as_string(), name.as_f64()) {
+                    (Some(name), _) => format!("Entry not found: {}", name),
+                    (None, Some(i)) => format!("Entry not found: #{}", i),
+                    (None, None) => "Entry not found.".to_string(),
+                };
+                Err(JsValue::from(Error::new(message.as_str())))
             }
         }
-        Err(JsValue::from(Error::new("Entry not found.")))
+    }
+
+    // ファイル名の変換に使うエンコーディング名。"auto" の場合は parseCD 後に推定結果が入る
+    #[wasm_bindgen(getter)]
+    pub fn encoding(&self) -> String {
+        self.encoding.name().to_string()
+    }
+
+    // アーカイブのコメント。ファイル名と同じエンコーディングで変換する
+    #[wasm_bindgen(getter)]
+    pub fn comment(&self) -> String {
+        self.eocd.decoded_comment(self.encoding)
+    }
+
+    #[wasm_bindgen(getter, js_name=rawComment)]
+    pub fn raw_comment(&self) -> Vec<u8> {
+        self.eocd.comment.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name=baseOffset)]
+    pub fn base_offset(&self) -> f64 {
+        self.base_offset as f64
     }
 
     #[wasm_bindgen(getter, js_name=cdRange)]
     pub fn cd_range(&self) -> Range {
         Range {
-            offset: self.eocd.cd_offset,
+            offset: self.base_offset + self.eocd.cd_offset,
             size: self.eocd.cd_size,
         }
     }
 
+    // EOCD の範囲は new に渡したデータの中での位置
     #[wasm_bindgen(getter, js_name=eocdRange)]
     pub fn eocd_range(&self) -> Range {
         Range {
@@ -124,6 +507,13 @@ impl LSZR {
     }
 }
 
+// multipart/byteranges のレスポンスを、各部分のファイル上の位置とデータに分ける。
+// 各部分は planRanges で求めた範囲のように、そのまま getData などに渡せる
+#[wasm_bindgen(js_name = parseByteRanges)]
+pub fn parse_byte_ranges(boundary: &str, body: &[u8]) -> Result<Vec<ByteRangeSegment>, JsValue> {
+    Ok(multipart::parse_byteranges(body, boundary)?)
+}
+
 impl From<zip::ParseEOCDError> for JsValue {
     fn from(err: zip::ParseEOCDError) -> Self {
         JsValue::from(Error::new(
@@ -135,6 +525,15 @@ impl From<zip::ParseEOCDError> for JsValue {
                 zip::ParseEOCDError::TooShortDataLength => {
                     "ParseEOCDError: TooShortDataLength".to_string()
                 }
+                zip::ParseEOCDError::CommentLengthMismatch => {
+                    "ParseEOCDError: CommentLengthMismatch".to_string()
+                }
+                zip::ParseEOCDError::ImplausibleRecord => {
+                    "ParseEOCDError: ImplausibleRecord".to_string()
+                }
+                zip::ParseEOCDError::Zip64RecordNotFound => {
+                    "ParseEOCDError: Zip64RecordNotFound".to_string()
+                }
             }
             .as_str(),
         ))
@@ -146,9 +545,6 @@ impl From<zip::ParseCDError> for JsValue {
         JsValue::from(Error::new(
             match err {
                 zip::ParseCDError::IOError(err) => format!("ParseCDError: {}", err),
-                zip::ParseCDError::FileNameConversionError => {
-                    format!("ParseCDError: FileNameConversionError")
-                }
                 zip::ParseCDError::InvalidSignature => "ParseCDError: InvalidSignature".to_string(),
             }
             .as_str(),
@@ -164,9 +560,70 @@ impl From<zip::LoadFileError> for JsValue {
                 zip::LoadFileError::InvalidSignature => "LoadFileError: InvalidSignature".to_string(),
                 zip::LoadFileError::UnmatchHeader => "LoadFileError: UnmatchHeader".to_string(),
                 zip::LoadFileError::UnsupportedCompressionMethod(m) => format!("LoadFileError: UnsupportedCompressionMethod: {}", m),
-                zip::LoadFileError::FileNameConversionError => "LoadFileError: FileNameConversionError".to_string(),
+                zip::LoadFileError::CrcMismatch { expected, actual } => format!("LoadFileError: CrcMismatch: expected {:08x}, actual {:08x}", expected, actual),
+                zip::LoadFileError::PasswordRequired => "LoadFileError: PasswordRequired".to_string(),
+                zip::LoadFileError::WrongPassword => "LoadFileError: WrongPassword".to_string(),
+                zip::LoadFileError::AuthenticationFailed => "LoadFileError: AuthenticationFailed".to_string(),
+                zip::LoadFileError::InvalidAesExtraField => "LoadFileError: InvalidAesExtraField".to_string(),
+                zip::LoadFileError::ChunkNotCovered => "LoadFileError: ChunkNotCovered".to_string(),
             }
             .as_str(),
         ))
     }
 }
+
+impl From<snapshot::SnapshotError> for JsValue {
+    fn from(err: snapshot::SnapshotError) -> Self {
+        JsValue::from(Error::new(
+            match err {
+                snapshot::SnapshotError::IOError(err) => format!("SnapshotError: {}", err),
+                snapshot::SnapshotError::InvalidMagic => "SnapshotError: InvalidMagic".to_string(),
+                snapshot::SnapshotError::UnsupportedVersion(v) => format!("SnapshotError: UnsupportedVersion: {}", v),
+                snapshot::SnapshotError::ChecksumMismatch => "SnapshotError: ChecksumMismatch".to_string(),
+                snapshot::SnapshotError::Corrupted => "SnapshotError: Corrupted".to_string(),
+            }
+            .as_str(),
+        ))
+    }
+}
+
+impl From<multipart::MultipartError> for JsValue {
+    fn from(err: multipart::MultipartError) -> Self {
+        JsValue::from(Error::new(
+            match err {
+                multipart::MultipartError::BoundaryNotFound => "MultipartError: BoundaryNotFound",
+                multipart::MultipartError::MissingContentRange => "MultipartError: MissingContentRange",
+                multipart::MultipartError::InvalidContentRange => "MultipartError: InvalidContentRange",
+                multipart::MultipartError::UnexpectedEnd => "MultipartError: UnexpectedEnd",
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::tests::{create_cd_header, create_local_file_header, create_minimal_eocd};
+
+    #[test]
+    fn test_new_with_prepended_stub() {
+        // 自己解凍形式のように、書かれているオフセットはスタブを含まない
+        let stub = vec![0x90u8; 100_000];
+        let content = b"Hello, World!";
+        let crc = crc32fast::hash(content);
+        let lfh = create_local_file_header("test.txt", content, zip::COMPRESSION_METHOD_STORED, crc, true);
+        let cd = create_cd_header("test.txt", 0, content.len() as u32, content.len() as u32, crc, zip::COMPRESSION_METHOD_STORED, true);
+        let eocd = create_minimal_eocd(1, cd.len() as u32, lfh.len() as u32);
+        let archive = [stub.clone(), lfh.clone(), cd.clone(), eocd].concat();
+
+        // ラッパーと同じように末尾の 65557 バイトだけを渡す
+        let tail_offset = archive.len() - 65557;
+        let lszr = LSZR::new(archive[tail_offset..].to_vec(), None, Some(tail_offset as f64)).unwrap();
+        assert_eq!(lszr.base_offset(), stub.len() as f64);
+        let cd_range = lszr.cd_range();
+        assert_eq!(cd_range.offset(), (stub.len() + lfh.len()) as f64);
+        assert_eq!(cd_range.size(), cd.len() as f64);
+        // EOCD の範囲は渡したデータの中での位置のまま
+        assert_eq!(lszr.eocd_range().offset(), (archive.len() - 22 - tail_offset) as f64);
+    }
+}
@@ -0,0 +1,201 @@
+use wasm_bindgen::prelude::*;
+
+// multipart/byteranges の 1 つの部分。offset はファイル先頭からの位置
+#[wasm_bindgen]
+pub struct ByteRangeSegment {
+    offset: u64,
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ByteRangeSegment {
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> f64 {
+        self.offset as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+#[derive(Debug)]
+pub enum MultipartError {
+    BoundaryNotFound,
+    MissingContentRange,
+    InvalidContentRange,
+    UnexpectedEnd,
+}
+
+// boundary には Content-Type ヘッダの値（multipart/byteranges; boundary=...）をそのまま渡してもよい。
+// 各部分の長さは Content-Range から求めるので、データの中に区切り文字列が現れても問題ない
+pub fn parse_byteranges(body: &[u8], boundary: &str) -> Result<Vec<ByteRangeSegment>, MultipartError> {
+    let delimiter = format!("--{}", normalize_boundary(boundary)).into_bytes();
+    let mut position = find(body, &delimiter, 0).ok_or(MultipartError::BoundaryNotFound)?;
+    let mut segments = Vec::new();
+    loop {
+        position += delimiter.len();
+        // 終端の区切り
+        if body[position..].starts_with(b"--") {
+            return Result::Ok(segments);
+        }
+        // 区切りの行の残り
+        let (_, next) = read_line(body, position)?;
+        position = next;
+
+        let mut range = None;
+        loop {
+            let (line, next) = read_line(body, position)?;
+            position = next;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = header_value(line, "content-range") {
+                range = Some(parse_content_range(value)?);
+            }
+        }
+        let (start, end) = range.ok_or(MultipartError::MissingContentRange)?;
+        // wasm32 では usize に収まらない長さもある
+        let length = (end - start)
+            .checked_add(1)
+            .and_then(|length| usize::try_from(length).ok())
+            .ok_or(MultipartError::InvalidContentRange)?;
+        let data_end = position.checked_add(length).ok_or(MultipartError::UnexpectedEnd)?;
+        let data = body.get(position..data_end).ok_or(MultipartError::UnexpectedEnd)?;
+        segments.push(ByteRangeSegment {
+            offset: start,
+            data: data.to_vec(),
+        });
+        position = find(body, &delimiter, data_end).ok_or(MultipartError::UnexpectedEnd)?;
+    }
+}
+
+fn normalize_boundary(value: &str) -> &str {
+    let boundary = value
+        .split(';')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map_or(value, |(_, boundary)| boundary);
+    boundary.trim().trim_matches('"')
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|i| from + i)
+}
+
+// 改行（CRLF または LF）までを返す。改行がなければデータが途中で切れている
+fn read_line(body: &[u8], position: usize) -> Result<(&[u8], usize), MultipartError> {
+    let end = find(body, b"\n", position).ok_or(MultipartError::UnexpectedEnd)?;
+    let line = &body[position..end];
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    Result::Ok((line, end + 1))
+}
+
+fn header_value<'a>(line: &'a [u8], name: &str) -> Option<&'a str> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (key, value) = line.split_once(':')?;
+    if key.trim().eq_ignore_ascii_case(name) {
+        Some(value.trim())
+    } else {
+        None
+    }
+}
+
+// "bytes 100-199/1000" の形式。全体の長さは "*" の場合もある
+fn parse_content_range(value: &str) -> Result<(u64, u64), MultipartError> {
+    let range = value
+        .strip_prefix("bytes")
+        .map(str::trim_start)
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|range| range.split_once('-'))
+        .ok_or(MultipartError::InvalidContentRange)?;
+    let start = range.0.trim().parse::<u64>();
+    let end = range.1.trim().parse::<u64>();
+    match (start, end) {
+        (Result::Ok(start), Result::Ok(end)) if start <= end => Result::Ok((start, end)),
+        _ => Result::Err(MultipartError::InvalidContentRange),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Helper: multipart/byteranges のレスポンスを生成する
+    fn create_body(boundary: &str, parts: &[(u64, &[u8])]) -> Vec<u8> {
+        let mut body = b"preamble\r\n".to_vec();
+        for (offset, data) in parts {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            body.extend_from_slice(b"Content-Type: application/zip\r\n");
+            let end = offset + data.len() as u64 - 1;
+            body.extend_from_slice(format!("Content-Range: bytes {}-{}/100000\r\n\r\n", offset, end).as_bytes());
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        body
+    }
+
+    #[test]
+    fn test_parse_byteranges() {
+        let boundary = "3d6b6a416f9b5";
+        // データの中に区切り文字列が含まれていても Content-Range の長さで切り出す
+        let tricky = b"PK\x03\x04\r\n--3d6b6a416f9b5\r\n";
+        let body = create_body(boundary, &[(0, b"PK\x03\x04hello"), (5000, tricky)]);
+
+        let segments = parse_byteranges(&body, boundary).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].offset, 0);
+        assert_eq!(segments[0].data, b"PK\x03\x04hello");
+        assert_eq!(segments[1].offset, 5000);
+        assert_eq!(segments[1].data, tricky);
+    }
+
+    #[test]
+    fn test_parse_byteranges_content_type_and_lf() {
+        let body = b"--abc\nCONTENT-RANGE: bytes 10-12/*\n\nxyz\n--abc--";
+        let segments = parse_byteranges(body, "multipart/byteranges; boundary=\"abc\"").unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].offset, 10);
+        assert_eq!(segments[0].data, b"xyz");
+    }
+
+    #[test]
+    fn test_parse_byteranges_errors() {
+        let body = create_body("abc", &[(0, b"hello")]);
+        assert!(matches!(parse_byteranges(&body, "xyz"), Err(MultipartError::BoundaryNotFound)));
+
+        // 途中で切れている
+        let truncated = &body[..body.len() - 12];
+        assert!(matches!(parse_byteranges(truncated, "abc"), Err(MultipartError::UnexpectedEnd)));
+
+        let body = b"--abc\r\nContent-Type: text/plain\r\n\r\nhello\r\n--abc--";
+        assert!(matches!(parse_byteranges(body, "abc"), Err(MultipartError::MissingContentRange)));
+
+        let body = b"--abc\r\nContent-Range: bytes 9-1/100\r\n\r\nhello\r\n--abc--";
+        assert!(matches!(parse_byteranges(body, "abc"), Err(MultipartError::InvalidContentRange)));
+    }
+
+    #[test]
+    fn test_parse_byteranges_oversized_range() {
+        // 長さが u64 に収まらない
+        let body = b"--abc\r\nContent-Range: bytes 0-18446744073709551615/*\r\n\r\nhello\r\n--abc--";
+        assert!(matches!(parse_byteranges(body, "abc"), Err(MultipartError::InvalidContentRange)));
+
+        // 長さ 4 GiB の範囲は wasm32 では usize に収まらず、64 ビット環境では本文が足りない
+        let body = b"--abc\r\nContent-Range: bytes 4294967296-8589934591/*\r\n\r\nhello\r\n--abc--";
+        #[cfg(target_pointer_width = "32")]
+        assert!(matches!(parse_byteranges(body, "abc"), Err(MultipartError::InvalidContentRange)));
+        #[cfg(target_pointer_width = "64")]
+        assert!(matches!(parse_byteranges(body, "abc"), Err(MultipartError::UnexpectedEnd)));
+
+        // どの環境でも usize に収まるが、本文より長い範囲
+        let body = b"--abc\r\nContent-Range: bytes 0-99/*\r\n\r\nhello\r\n--abc--";
+        assert!(matches!(parse_byteranges(body, "abc"), Err(MultipartError::UnexpectedEnd)));
+    }
+}
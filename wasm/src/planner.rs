@@ -0,0 +1,172 @@
+use std::cmp;
+use wasm_bindgen::prelude::*;
+
+// 複数のエントリをまとめて取得する 1 回分の範囲。size はバイト数そのもの
+#[wasm_bindgen]
+pub struct RangePlan {
+    offset: u64,
+    size: u64,
+    slices: Vec<RangeSlice>,
+}
+
+// まとめて取得したデータから 1 つのエントリを切り出す位置
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeSlice {
+    index: usize,
+    offset: u64,
+    size: u64,
+}
+
+#[wasm_bindgen]
+impl RangePlan {
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> f64 {
+        self.offset as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> f64 {
+        self.size as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn slices(&self) -> Vec<RangeSlice> {
+        self.slices.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl RangeSlice {
+    // planRanges に渡した names の中での位置
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    // まとめて取得したデータの先頭からの位置
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> f64 {
+        self.offset as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> f64 {
+        self.size as f64
+    }
+}
+
+// spans は要求された順番に並んだ各エントリの (ファイル上の位置, 長さ)。
+// 位置順に並べ、前の範囲との隙間が max_gap 以下で、まとめても max_range_bytes を超えないものを 1 つの範囲にする。
+// 1 つで max_range_bytes を超えるエントリはそのまま単独の範囲になる
+pub fn plan_ranges(spans: &[(u64, u64)], max_gap: u64, max_range_bytes: u64) -> Vec<RangePlan> {
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| spans[i]);
+
+    let mut plans: Vec<RangePlan> = Vec::new();
+    for index in order {
+        let (offset, size) = spans[index];
+        let end = offset.saturating_add(size);
+        if let Some(plan) = plans.last_mut() {
+            let plan_end = plan.offset.saturating_add(plan.size);
+            let merged_end = cmp::max(plan_end, end);
+            if offset <= plan_end.saturating_add(max_gap) && merged_end - plan.offset <= max_range_bytes {
+                plan.size = merged_end - plan.offset;
+                plan.slices.push(RangeSlice {
+                    index,
+                    offset: offset - plan.offset,
+                    size,
+                });
+                continue;
+            }
+        }
+        plans.push(RangePlan {
+            offset,
+            size,
+            slices: vec![RangeSlice {
+                index,
+                offset: 0,
+                size,
+            }],
+        });
+    }
+    plans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (offset, size, 各スライスの (index, offset, size))
+    type PlanSummary = (u64, u64, Vec<(usize, u64, u64)>);
+
+    // Helper: 比較しやすい形に変換する
+    fn summarize(plans: &[RangePlan]) -> Vec<PlanSummary> {
+        plans
+            .iter()
+            .map(|p| {
+                let slices = p.slices.iter().map(|s| (s.index, s.offset, s.size)).collect();
+                (p.offset, p.size, slices)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_ranges_merges_near_entries() {
+        // 要求順と位置順が異なる
+        let spans = [(300, 50), (0, 100), (110, 40), (1000, 10)];
+        let plans = plan_ranges(&spans, 10, u64::MAX);
+        assert_eq!(
+            summarize(&plans),
+            vec![
+                (0, 150, vec![(1, 0, 100), (2, 110, 40)]),
+                (300, 50, vec![(0, 0, 50)]),
+                (1000, 10, vec![(3, 0, 10)]),
+            ]
+        );
+
+        // 隙間を許容すればすべてまとまる
+        let plans = plan_ranges(&spans, 1000, u64::MAX);
+        assert_eq!(plans.len(), 1);
+        assert_eq!((plans[0].offset, plans[0].size), (0, 1010));
+        assert_eq!(plans[0].slices[3], RangeSlice { index: 3, offset: 1000, size: 10 });
+    }
+
+    #[test]
+    fn test_plan_ranges_respects_max_range_bytes() {
+        let spans = [(0, 100), (100, 100), (200, 100), (300, 500)];
+        let plans = plan_ranges(&spans, 0, 250);
+        assert_eq!(
+            summarize(&plans),
+            vec![
+                (0, 200, vec![(0, 0, 100), (1, 100, 100)]),
+                (200, 100, vec![(2, 0, 100)]),
+                // 単独で上限を超えるエントリ
+                (300, 500, vec![(3, 0, 500)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_ranges_overlapping_and_duplicate_spans() {
+        // 拡張フィールドの見込み分が次のエントリに重なる場合や、同じエントリを 2 回要求した場合
+        let spans = [(0, 120), (100, 50), (0, 120)];
+        let plans = plan_ranges(&spans, 0, u64::MAX);
+        assert_eq!(
+            summarize(&plans),
+            vec![(0, 150, vec![(0, 0, 120), (2, 0, 120), (1, 100, 50)])]
+        );
+        assert!(plan_ranges(&[], 0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_plan_ranges_bogus_sizes() {
+        // 壊れた CD のサイズでも桁あふれしない
+        let spans = [(100, u64::MAX), (200, 10)];
+        let plans = plan_ranges(&spans, 0, u64::MAX);
+        assert_eq!(
+            summarize(&plans),
+            vec![(100, u64::MAX - 100, vec![(0, 0, u64::MAX), (1, 100, 10)])]
+        );
+    }
+}
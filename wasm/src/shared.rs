@@ -0,0 +1,573 @@
+use crate::encoding::LegacyEncoding;
+use crate::snapshot::{self, Snapshot, SnapshotError};
+use crate::zip::{AesExtraField, CDHeader, CD_SIGNATURE, EOCD};
+use podio::{LittleEndian, ReadPodExt};
+use std::io;
+
+// 複数の LSZR から読み取り専用で共有するためのインデックスの形式。
+// 展開せずにそのまま引けるよう、固定長のレコードと名前のハッシュ表を持つ。数値はすべてリトルエンディアン
+//
+//   ヘッダ (48)
+//   メタデータ: エントリを含まない snapshot 形式の EOCD とエンコーディング
+//   レコード: エントリごとに RECORD_LENGTH バイト（CD の順番）
+//   名前のハッシュ表 / 生の名前のハッシュ表: スロットごとに u32（エントリの番号 + 1、0 は空き）
+//   オフセット順の表: (ローカルヘッダのオフセット u64, エントリの番号 u32)
+//   文字列領域: 名前、拡張フィールド、コメントのバイト列
+pub const SHARED_INDEX_MAGIC: [u8; 4] = *b"LSZS";
+pub const SHARED_INDEX_VERSION: u16 = 1;
+const HEADER_LENGTH: usize = 48;
+const RECORD_LENGTH: usize = 88;
+const SLOT_LENGTH: usize = 4;
+const ORDER_LENGTH: usize = 12;
+
+// レコード内の位置
+const RECORD_FILE_NAME: usize = 50;
+const RECORD_FILE_NAME_BYTES: usize = 58;
+
+const FLAG_UTF8: u8 = 1;
+const FLAG_ENCRYPTED: u8 = 1 << 1;
+const FLAG_ZIP64: u8 = 1 << 2;
+const FLAG_NAME_LOSSY: u8 = 1 << 3;
+const FLAG_AES: u8 = 1 << 4;
+
+// インデックスを読み出すバッファ。JS 側の SharedArrayBuffer などはコピーせず、必要な部分だけを読む
+pub trait IndexBuffer {
+    fn byte_length(&self) -> usize;
+    // offset から buf の長さ分を読む。範囲は呼び出し側で確認する
+    fn read_at(&self, offset: usize, buf: &mut [u8]);
+}
+
+impl IndexBuffer for Vec<u8> {
+    fn byte_length(&self) -> usize {
+        self.len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self[offset..offset + buf.len()]);
+    }
+}
+
+impl IndexBuffer for js_sys::Uint8Array {
+    fn byte_length(&self) -> usize {
+        self.length() as usize
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) {
+        let end = offset + buf.len();
+        self.subarray(offset as u32, end as u32).copy_to(buf);
+    }
+}
+
+pub fn build(
+    eocd: &EOCD,
+    base_offset: u64,
+    entries: &[CDHeader],
+    requested_encoding: Option<LegacyEncoding>,
+    encoding: LegacyEncoding,
+) -> Vec<u8> {
+    let meta = snapshot::serialize(eocd, base_offset, &[], requested_encoding, encoding);
+    let slot_count = slot_count(entries.len());
+
+    let mut strings = Vec::new();
+    let mut records = Vec::with_capacity(entries.len() * RECORD_LENGTH);
+    for cdh in entries {
+        let mut flags = 0;
+        if cdh.is_utf8 {
+            flags |= FLAG_UTF8;
+        }
+        if cdh.is_encrypted {
+            flags |= FLAG_ENCRYPTED;
+        }
+        if cdh.is_zip64 {
+            flags |= FLAG_ZIP64;
+        }
+        if cdh.is_name_lossy {
+            flags |= FLAG_NAME_LOSSY;
+        }
+        if cdh.aes.is_some() {
+            flags |= FLAG_AES;
+        }
+        let aes = cdh.aes.unwrap_or(AesExtraField {
+            vendor_version: 0,
+            strength: 0,
+            compression_method: 0,
+        });
+
+        put_u16(&mut records, cdh.version_made_by);
+        put_u16(&mut records, cdh.version_needed_to_extract);
+        put_u16(&mut records, cdh.general_purpose_bit_flag);
+        put_u16(&mut records, cdh.compression_method);
+        put_u16(&mut records, cdh.last_mod_file_time);
+        put_u16(&mut records, cdh.last_mod_file_date);
+        put_u32(&mut records, cdh.crc32);
+        put_u64(&mut records, cdh.compressed_size);
+        put_u64(&mut records, cdh.uncompressed_size);
+        put_u32(&mut records, cdh.disk_number_start);
+        put_u16(&mut records, cdh.internal_file_attributes);
+        put_u32(&mut records, cdh.external_file_attributes);
+        put_u64(&mut records, cdh.relative_offset_of_local_header);
+        for value in [
+            cdh.file_name.as_bytes(),
+            &cdh.file_name_bytes,
+            &cdh.extra_field,
+            &cdh.file_comment,
+        ] {
+            put_u32(&mut records, strings.len() as u32);
+            put_u32(&mut records, value.len() as u32);
+            strings.extend_from_slice(value);
+        }
+        records.push(flags);
+        put_u16(&mut records, aes.vendor_version);
+        records.push(aes.strength);
+        put_u16(&mut records, aes.compression_method);
+    }
+
+    let name_slots = build_slots(entries.iter().map(|e| e.file_name.as_bytes()), slot_count);
+    let raw_name_slots = build_slots(entries.iter().map(|e| e.file_name_bytes.as_slice()), slot_count);
+
+    let mut order: Vec<(u64, usize)> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.relative_offset_of_local_header, i))
+        .collect();
+    order.sort_unstable();
+
+    let meta_offset = HEADER_LENGTH;
+    let records_offset = meta_offset + meta.len();
+    let name_slots_offset = records_offset + records.len();
+    let raw_name_slots_offset = name_slots_offset + slot_count * SLOT_LENGTH;
+    let order_offset = raw_name_slots_offset + slot_count * SLOT_LENGTH;
+    let strings_offset = order_offset + order.len() * ORDER_LENGTH;
+
+    let mut buf = SHARED_INDEX_MAGIC.to_vec();
+    put_u16(&mut buf, SHARED_INDEX_VERSION);
+    put_u16(&mut buf, 0);
+    for value in [
+        entries.len(),
+        slot_count,
+        meta_offset,
+        meta.len(),
+        records_offset,
+        name_slots_offset,
+        raw_name_slots_offset,
+        order_offset,
+        strings_offset,
+        strings.len(),
+    ] {
+        put_u32(&mut buf, value as u32);
+    }
+    buf.extend(meta);
+    buf.extend(records);
+    for slot in name_slots.into_iter().chain(raw_name_slots) {
+        put_u32(&mut buf, slot);
+    }
+    for (offset, i) in order {
+        put_u64(&mut buf, offset);
+        put_u32(&mut buf, i as u32);
+    }
+    buf.extend(strings);
+    buf
+}
+
+// build で作ったバッファに接続したインデックス。エントリは要求されたときにバッファから読む
+pub struct SharedIndex<B: IndexBuffer> {
+    buffer: B,
+    meta: Snapshot,
+    entry_count: usize,
+    slot_count: usize,
+    records_offset: usize,
+    name_slots_offset: usize,
+    raw_name_slots_offset: usize,
+    order_offset: usize,
+    strings_offset: usize,
+    strings_length: usize,
+}
+
+impl<B: IndexBuffer> SharedIndex<B> {
+    // ヘッダとメタデータだけを読み、各領域がバッファに収まっていることを確認する
+    pub fn attach(buffer: B) -> Result<SharedIndex<B>, SnapshotError> {
+        let length = buffer.byte_length();
+        if length < HEADER_LENGTH {
+            return Result::Err(SnapshotError::Corrupted);
+        }
+        let mut header = [0; HEADER_LENGTH];
+        buffer.read_at(0, &mut header);
+        if header[..4] != SHARED_INDEX_MAGIC {
+            return Result::Err(SnapshotError::InvalidMagic);
+        }
+        let mut reader = io::Cursor::new(&header[4..]);
+        let version = reader.read_u16::<LittleEndian>()?;
+        if version != SHARED_INDEX_VERSION {
+            return Result::Err(SnapshotError::UnsupportedVersion(version));
+        }
+        reader.read_u16::<LittleEndian>()?;
+        let mut fields = [0; 10];
+        for field in fields.iter_mut() {
+            *field = reader.read_u32::<LittleEndian>()? as usize;
+        }
+        let [entry_count, slot_count, meta_offset, meta_length, records_offset, name_slots_offset, raw_name_slots_offset, order_offset, strings_offset, strings_length] =
+            fields;
+
+        // 壊れたヘッダの大きな件数で桁あふれしないよう、領域の長さも検査しながら求める
+        let regions = [
+            (meta_offset, Some(meta_length)),
+            (records_offset, entry_count.checked_mul(RECORD_LENGTH)),
+            (name_slots_offset, slot_count.checked_mul(SLOT_LENGTH)),
+            (raw_name_slots_offset, slot_count.checked_mul(SLOT_LENGTH)),
+            (order_offset, entry_count.checked_mul(ORDER_LENGTH)),
+            (strings_offset, Some(strings_length)),
+        ];
+        let fits = regions.iter().all(|&(offset, size)| {
+            size.and_then(|size| offset.checked_add(size))
+                .is_some_and(|end| end <= length)
+        });
+        if !fits || slot_count < entry_count || (entry_count > 0 && !slot_count.is_power_of_two()) {
+            return Result::Err(SnapshotError::Corrupted);
+        }
+
+        let mut meta = vec![0; meta_length];
+        buffer.read_at(meta_offset, &mut meta);
+        let meta = snapshot::deserialize(&meta)?;
+
+        Result::Ok(SharedIndex {
+            buffer,
+            meta,
+            entry_count,
+            slot_count,
+            records_offset,
+            name_slots_offset,
+            raw_name_slots_offset,
+            order_offset,
+            strings_offset,
+            strings_length,
+        })
+    }
+
+    pub fn buffer(&self) -> &B {
+        &self.buffer
+    }
+
+    pub fn eocd(&self) -> &EOCD {
+        &self.meta.eocd
+    }
+
+    pub fn base_offset(&self) -> u64 {
+        self.meta.base_offset
+    }
+
+    pub fn requested_encoding(&self) -> Option<LegacyEncoding> {
+        self.meta.requested_encoding
+    }
+
+    pub fn encoding(&self) -> LegacyEncoding {
+        self.meta.encoding
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+
+    pub fn entry(&self, index: usize) -> Result<CDHeader, SnapshotError> {
+        let record = self.read_record(index)?;
+        let mut reader = io::Cursor::new(&record[..]);
+        let version_made_by = reader.read_u16::<LittleEndian>()?;
+        let version_needed_to_extract = reader.read_u16::<LittleEndian>()?;
+        let general_purpose_bit_flag = reader.read_u16::<LittleEndian>()?;
+        let compression_method = reader.read_u16::<LittleEndian>()?;
+        let last_mod_file_time = reader.read_u16::<LittleEndian>()?;
+        let last_mod_file_date = reader.read_u16::<LittleEndian>()?;
+        let crc32 = reader.read_u32::<LittleEndian>()?;
+        let compressed_size = reader.read_u64::<LittleEndian>()?;
+        let uncompressed_size = reader.read_u64::<LittleEndian>()?;
+        let disk_number_start = reader.read_u32::<LittleEndian>()?;
+        let internal_file_attributes = reader.read_u16::<LittleEndian>()?;
+        let external_file_attributes = reader.read_u32::<LittleEndian>()?;
+        let relative_offset_of_local_header = reader.read_u64::<LittleEndian>()?;
+        let file_name = String::from_utf8(self.read_string(&mut reader)?).map_err(|_| SnapshotError::Corrupted)?;
+        let file_name_bytes = self.read_string(&mut reader)?;
+        let extra_field = self.read_string(&mut reader)?;
+        let file_comment = self.read_string(&mut reader)?;
+        let flags = reader.read_u8()?;
+        let aes = AesExtraField {
+            vendor_version: reader.read_u16::<LittleEndian>()?,
+            strength: reader.read_u8()?,
+            compression_method: reader.read_u16::<LittleEndian>()?,
+        };
+
+        Result::Ok(CDHeader {
+            signature: CD_SIGNATURE,
+            version_made_by,
+            version_needed_to_extract,
+            general_purpose_bit_flag,
+            compression_method,
+            last_mod_file_time,
+            last_mod_file_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            file_name_length: file_name_bytes.len() as u16,
+            extra_field_length: extra_field.len() as u16,
+            file_comment_length: file_comment.len() as u16,
+            disk_number_start,
+            internal_file_attributes,
+            external_file_attributes,
+            relative_offset_of_local_header,
+            file_name,
+            file_name_bytes,
+            is_name_lossy: flags & FLAG_NAME_LOSSY != 0,
+            extra_field,
+            file_comment,
+            is_utf8: flags & FLAG_UTF8 != 0,
+            is_encrypted: flags & FLAG_ENCRYPTED != 0,
+            is_zip64: flags & FLAG_ZIP64 != 0,
+            aes: if flags & FLAG_AES != 0 { Some(aes) } else { None },
+        })
+    }
+
+    pub fn find(&self, name: &str) -> Result<Option<usize>, SnapshotError> {
+        self.find_in(self.name_slots_offset, RECORD_FILE_NAME, name.as_bytes())
+    }
+
+    pub fn find_raw(&self, raw_name: &[u8]) -> Result<Option<usize>, SnapshotError> {
+        self.find_in(self.raw_name_slots_offset, RECORD_FILE_NAME_BYTES, raw_name)
+    }
+
+    // offset より後ろにある最初のローカルヘッダのオフセットを返す
+    pub fn next_offset(&self, offset: u64) -> Option<u64> {
+        let (mut low, mut high) = (0, self.entry_count);
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.order_offset_at(mid) <= offset {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low < self.entry_count {
+            Some(self.order_offset_at(low))
+        } else {
+            None
+        }
+    }
+
+    fn find_in(&self, slots_offset: usize, field: usize, key: &[u8]) -> Result<Option<usize>, SnapshotError> {
+        if self.entry_count == 0 {
+            return Result::Ok(None);
+        }
+        let mask = self.slot_count - 1;
+        let mut slot = hash(key) as usize & mask;
+        // 壊れたバッファで空きスロットがなくても止まるよう、スロット数で打ち切る
+        for _ in 0..self.slot_count {
+            let value = self.read_u32(slots_offset + slot * SLOT_LENGTH);
+            if value == 0 {
+                return Result::Ok(None);
+            }
+            let index = value as usize - 1;
+            let record = self.read_record(index)?;
+            let mut reader = io::Cursor::new(&record[field..]);
+            if self.read_string(&mut reader)? == key {
+                return Result::Ok(Some(index));
+            }
+            slot = (slot + 1) & mask;
+        }
+        Result::Ok(None)
+    }
+
+    fn read_record(&self, index: usize) -> Result<[u8; RECORD_LENGTH], SnapshotError> {
+        if index >= self.entry_count {
+            return Result::Err(SnapshotError::Corrupted);
+        }
+        let mut record = [0; RECORD_LENGTH];
+        self.buffer.read_at(self.records_offset + index * RECORD_LENGTH, &mut record);
+        Result::Ok(record)
+    }
+
+    // レコード内の (文字列領域での位置 u32, 長さ u32) が指すバイト列を読む
+    fn read_string(&self, reader: &mut io::Cursor<&[u8]>) -> Result<Vec<u8>, SnapshotError> {
+        let offset = reader.read_u32::<LittleEndian>()? as usize;
+        let length = reader.read_u32::<LittleEndian>()? as usize;
+        if offset.checked_add(length).is_none_or(|end| end > self.strings_length) {
+            return Result::Err(SnapshotError::Corrupted);
+        }
+        let mut buf = vec![0; length];
+        self.buffer.read_at(self.strings_offset + offset, &mut buf);
+        Result::Ok(buf)
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        let mut buf = [0; 4];
+        self.buffer.read_at(offset, &mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    fn order_offset_at(&self, i: usize) -> u64 {
+        let mut buf = [0; 8];
+        self.buffer.read_at(self.order_offset + i * ORDER_LENGTH, &mut buf);
+        u64::from_le_bytes(buf)
+    }
+}
+
+// 使用率が半分以下になる 2 の累乗
+fn slot_count(entry_count: usize) -> usize {
+    if entry_count == 0 {
+        0
+    } else {
+        (entry_count * 2).next_power_of_two()
+    }
+}
+
+// 同じキーのエントリがある場合は先に現れたものを優先する
+fn build_slots<'a>(keys: impl Iterator<Item = &'a [u8]>, slot_count: usize) -> Vec<u32> {
+    let keys: Vec<&[u8]> = keys.collect();
+    let mut slots = vec![0u32; slot_count];
+    let mask = slot_count.wrapping_sub(1);
+    for (i, key) in keys.iter().enumerate() {
+        let mut slot = hash(key) as usize & mask;
+        loop {
+            match slots[slot] {
+                0 => {
+                    slots[slot] = i as u32 + 1;
+                    break;
+                }
+                value if keys[value as usize - 1] == *key => break,
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+    slots
+}
+
+// FNV-1a (32 ビット)
+fn hash(key: &[u8]) -> u32 {
+    key.iter()
+        .fold(0x811c9dc5u32, |h, &b| (h ^ b as u32).wrapping_mul(0x01000193))
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::EntryIndex;
+    use crate::zip::tests::create_cd_header;
+    use crate::zip::{parse_cd, COMPRESSION_METHOD_DEFLATED, COMPRESSION_METHOD_STORED};
+
+    // Helper: CD を解析した結果と、それから作った共有インデックスを返す
+    fn create_shared_index(count: usize) -> (Vec<CDHeader>, Vec<u8>) {
+        let mut cd_data = Vec::new();
+        // オフセット順と CD 順が逆になるように並べる
+        for i in (0..count).rev() {
+            let name = format!("pages/{:04}.jpg", i);
+            cd_data.extend(create_cd_header(&name, (i * 100) as u32, 90, 120, i as u32, COMPRESSION_METHOD_DEFLATED, true));
+        }
+        // 同名のエントリ
+        cd_data.extend(create_cd_header("pages/0000.jpg", 999_999, 1, 1, 0, COMPRESSION_METHOD_STORED, true));
+        let (mut entries, encoding) =
+            parse_cd(&mut io::Cursor::new(cd_data), count + 1, Some(LegacyEncoding::default())).unwrap();
+        entries[0].aes = Some(AesExtraField {
+            vendor_version: 2,
+            strength: 3,
+            compression_method: COMPRESSION_METHOD_DEFLATED,
+        });
+        entries[1].is_name_lossy = true;
+
+        let buffer = build(&create_eocd(entries.len() as u64), 64, &entries, None, encoding);
+        (entries, buffer)
+    }
+
+    // Helper: CD の位置だけを持つ EOCD
+    fn create_eocd(entry_count: u64) -> EOCD {
+        EOCD {
+            signature: 0x06054b50,
+            number_of_this_disk: 0,
+            number_of_disk_start_eocd: 0,
+            total_number_of_entries_on_disk: entry_count,
+            total_number_of_entries_in_cd: entry_count,
+            cd_size: 0,
+            cd_offset: 1_000_000,
+            comment: b"shared".to_vec(),
+            eocd_offset: 1_000_000,
+            eocd_size: 22,
+            is_zip64: false,
+        }
+    }
+
+    #[test]
+    fn test_shared_index_matches_parsed_entries() {
+        let (entries, buffer) = create_shared_index(200);
+        let shared = SharedIndex::attach(buffer).unwrap();
+        assert_eq!(shared.entry_count(), entries.len());
+        assert_eq!(shared.base_offset(), 64);
+        assert_eq!(shared.eocd().comment, b"shared");
+        assert_eq!(shared.requested_encoding(), None);
+
+        let index = EntryIndex::new(&entries);
+        for (i, entry) in entries.iter().enumerate() {
+            assert_eq!(format!("{:?}", shared.entry(i).unwrap()), format!("{:?}", entry));
+            assert_eq!(shared.find(&entry.file_name).unwrap(), index.find(&entry.file_name));
+            assert_eq!(shared.find_raw(&entry.file_name_bytes).unwrap(), index.find_raw(&entry.file_name_bytes));
+            let offset = entry.relative_offset_of_local_header;
+            assert_eq!(shared.next_offset(offset), index.next_offset(offset));
+        }
+        // 同名のエントリは先に現れたものを返す
+        assert_eq!(shared.find("pages/0000.jpg").unwrap(), Some(199));
+        assert_eq!(shared.find("pages/9999.jpg").unwrap(), None);
+        assert_eq!(shared.find_raw(b"missing").unwrap(), None);
+        assert!(shared.entry(entries.len()).is_err());
+    }
+
+    #[test]
+    fn test_shared_index_empty() {
+        let buffer = build(&create_eocd(0), 0, &[], Some(LegacyEncoding::default()), LegacyEncoding::default());
+        let empty = SharedIndex::attach(buffer).unwrap();
+        assert_eq!(empty.entry_count(), 0);
+        assert_eq!(empty.requested_encoding(), Some(LegacyEncoding::default()));
+        assert_eq!(empty.find("a.txt").unwrap(), None);
+        assert_eq!(empty.next_offset(0), None);
+    }
+
+    #[test]
+    fn test_shared_index_rejects_invalid_buffer() {
+        let (_, buffer) = create_shared_index(10);
+
+        let mut wrong_magic = buffer.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(SharedIndex::attach(wrong_magic), Err(SnapshotError::InvalidMagic)));
+
+        let mut newer = buffer.clone();
+        newer[4..6].copy_from_slice(&2u16.to_le_bytes());
+        assert!(matches!(SharedIndex::attach(newer), Err(SnapshotError::UnsupportedVersion(2))));
+
+        // 途中で切れている
+        let truncated = buffer[..buffer.len() - 1].to_vec();
+        assert!(matches!(SharedIndex::attach(truncated), Err(SnapshotError::Corrupted)));
+
+        // 件数が大きすぎる
+        let mut too_many = buffer.clone();
+        too_many[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(SharedIndex::attach(too_many), Err(SnapshotError::Corrupted)));
+
+        // メタデータが壊れている
+        let mut broken_meta = buffer.clone();
+        broken_meta[HEADER_LENGTH + 10] ^= 0x01;
+        assert!(matches!(SharedIndex::attach(broken_meta), Err(SnapshotError::ChecksumMismatch)));
+
+        // レコードの名前が文字列領域の外を指している
+        let records_offset = u32::from_le_bytes(buffer[24..28].try_into().unwrap()) as usize;
+        let mut broken_record = buffer.clone();
+        let field = records_offset + RECORD_FILE_NAME + 4;
+        broken_record[field..field + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let shared = SharedIndex::attach(broken_record).unwrap();
+        assert!(matches!(shared.entry(0), Err(SnapshotError::Corrupted)));
+    }
+}
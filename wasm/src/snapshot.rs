@@ -0,0 +1,356 @@
+use crate::encoding::LegacyEncoding;
+use crate::zip::{AesExtraField, CDHeader, EOCD};
+use podio::{LittleEndian, ReadPodExt};
+use std::io;
+
+// 解析済みのインデックスを保存した形式。
+// マジック (4) + バージョン (2) + 本体 + 先頭から本体までの CRC-32 (4)。数値はすべてリトルエンディアン
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"LSZI";
+pub const SNAPSHOT_VERSION: u16 = 1;
+const HEADER_LENGTH: usize = 4 + 2;
+const CHECKSUM_LENGTH: usize = 4;
+// 可変長の部分がすべて空の場合のエントリ 1 件の長さ
+const ENTRY_MIN_LENGTH: usize = 81;
+
+pub struct Snapshot {
+    pub eocd: EOCD,
+    pub base_offset: u64,
+    pub entries: Vec<CDHeader>,
+    // None は自動推定
+    pub requested_encoding: Option<LegacyEncoding>,
+    pub encoding: LegacyEncoding,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    // チェックサムは一致したが内容を解釈できない
+    Corrupted,
+    IOError(io::Error),
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::IOError(err)
+    }
+}
+
+pub fn serialize(
+    eocd: &EOCD,
+    base_offset: u64,
+    entries: &[CDHeader],
+    requested_encoding: Option<LegacyEncoding>,
+    encoding: LegacyEncoding,
+) -> Vec<u8> {
+    let mut buf = SNAPSHOT_MAGIC.to_vec();
+    put_u16(&mut buf, SNAPSHOT_VERSION);
+
+    put_u64(&mut buf, base_offset);
+    match requested_encoding {
+        Some(encoding) => put_bytes(&mut buf, encoding.name().as_bytes()),
+        None => put_bytes(&mut buf, b""),
+    }
+    put_bytes(&mut buf, encoding.name().as_bytes());
+
+    put_u32(&mut buf, eocd.signature);
+    put_u32(&mut buf, eocd.number_of_this_disk);
+    put_u32(&mut buf, eocd.number_of_disk_start_eocd);
+    put_u64(&mut buf, eocd.total_number_of_entries_on_disk);
+    put_u64(&mut buf, eocd.total_number_of_entries_in_cd);
+    put_u64(&mut buf, eocd.cd_size);
+    put_u64(&mut buf, eocd.cd_offset);
+    put_bytes(&mut buf, &eocd.comment);
+    put_u64(&mut buf, eocd.eocd_offset);
+    put_u64(&mut buf, eocd.eocd_size);
+    buf.push(eocd.is_zip64 as u8);
+
+    put_u64(&mut buf, entries.len() as u64);
+    for cdh in entries {
+        put_u32(&mut buf, cdh.signature);
+        put_u16(&mut buf, cdh.version_made_by);
+        put_u16(&mut buf, cdh.version_needed_to_extract);
+        put_u16(&mut buf, cdh.general_purpose_bit_flag);
+        put_u16(&mut buf, cdh.compression_method);
+        put_u16(&mut buf, cdh.last_mod_file_time);
+        put_u16(&mut buf, cdh.last_mod_file_date);
+        put_u32(&mut buf, cdh.crc32);
+        put_u64(&mut buf, cdh.compressed_size);
+        put_u64(&mut buf, cdh.uncompressed_size);
+        put_u16(&mut buf, cdh.file_name_length);
+        put_u16(&mut buf, cdh.extra_field_length);
+        put_u16(&mut buf, cdh.file_comment_length);
+        put_u32(&mut buf, cdh.disk_number_start);
+        put_u16(&mut buf, cdh.internal_file_attributes);
+        put_u32(&mut buf, cdh.external_file_attributes);
+        put_u64(&mut buf, cdh.relative_offset_of_local_header);
+        // 変換済みの名前も保存し、復元時にエンコーディングの推定や変換をやり直さない
+        put_bytes(&mut buf, cdh.file_name.as_bytes());
+        put_bytes(&mut buf, &cdh.file_name_bytes);
+        buf.push(cdh.is_name_lossy as u8);
+        put_bytes(&mut buf, &cdh.extra_field);
+        put_bytes(&mut buf, &cdh.file_comment);
+        buf.push(cdh.is_utf8 as u8);
+        buf.push(cdh.is_encrypted as u8);
+        buf.push(cdh.is_zip64 as u8);
+        match cdh.aes {
+            Some(aes) => {
+                buf.push(1);
+                put_u16(&mut buf, aes.vendor_version);
+                buf.push(aes.strength);
+                put_u16(&mut buf, aes.compression_method);
+            }
+            None => buf.push(0),
+        }
+    }
+
+    let checksum = crc32fast::hash(&buf);
+    put_u32(&mut buf, checksum);
+    buf
+}
+
+pub fn deserialize(data: &[u8]) -> Result<Snapshot, SnapshotError> {
+    if data.len() < HEADER_LENGTH + CHECKSUM_LENGTH {
+        return Result::Err(SnapshotError::Corrupted);
+    }
+    if data[..4] != SNAPSHOT_MAGIC {
+        return Result::Err(SnapshotError::InvalidMagic);
+    }
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version != SNAPSHOT_VERSION {
+        return Result::Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let (body, checksum) = data.split_at(data.len() - CHECKSUM_LENGTH);
+    if crc32fast::hash(body) != u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+        return Result::Err(SnapshotError::ChecksumMismatch);
+    }
+
+    let mut reader = io::Cursor::new(&body[HEADER_LENGTH..]);
+    let base_offset = reader.read_u64::<LittleEndian>()?;
+    let requested_encoding = match read_string(&mut reader)?.as_str() {
+        "" => None,
+        label => Some(LegacyEncoding::for_label(label).ok_or(SnapshotError::Corrupted)?),
+    };
+    let encoding = LegacyEncoding::for_label(&read_string(&mut reader)?).ok_or(SnapshotError::Corrupted)?;
+
+    let eocd = EOCD {
+        signature: reader.read_u32::<LittleEndian>()?,
+        number_of_this_disk: reader.read_u32::<LittleEndian>()?,
+        number_of_disk_start_eocd: reader.read_u32::<LittleEndian>()?,
+        total_number_of_entries_on_disk: reader.read_u64::<LittleEndian>()?,
+        total_number_of_entries_in_cd: reader.read_u64::<LittleEndian>()?,
+        cd_size: reader.read_u64::<LittleEndian>()?,
+        cd_offset: reader.read_u64::<LittleEndian>()?,
+        comment: read_bytes(&mut reader)?,
+        eocd_offset: reader.read_u64::<LittleEndian>()?,
+        eocd_size: reader.read_u64::<LittleEndian>()?,
+        is_zip64: read_bool(&mut reader)?,
+    };
+
+    let count = reader.read_u64::<LittleEndian>()?;
+    // 残りの長さに収まらない件数は壊れている
+    if count > (body.len() / ENTRY_MIN_LENGTH) as u64 {
+        return Result::Err(SnapshotError::Corrupted);
+    }
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        entries.push(CDHeader {
+            signature: reader.read_u32::<LittleEndian>()?,
+            version_made_by: reader.read_u16::<LittleEndian>()?,
+            version_needed_to_extract: reader.read_u16::<LittleEndian>()?,
+            general_purpose_bit_flag: reader.read_u16::<LittleEndian>()?,
+            compression_method: reader.read_u16::<LittleEndian>()?,
+            last_mod_file_time: reader.read_u16::<LittleEndian>()?,
+            last_mod_file_date: reader.read_u16::<LittleEndian>()?,
+            crc32: reader.read_u32::<LittleEndian>()?,
+            compressed_size: reader.read_u64::<LittleEndian>()?,
+            uncompressed_size: reader.read_u64::<LittleEndian>()?,
+            file_name_length: reader.read_u16::<LittleEndian>()?,
+            extra_field_length: reader.read_u16::<LittleEndian>()?,
+            file_comment_length: reader.read_u16::<LittleEndian>()?,
+            disk_number_start: reader.read_u32::<LittleEndian>()?,
+            internal_file_attributes: reader.read_u16::<LittleEndian>()?,
+            external_file_attributes: reader.read_u32::<LittleEndian>()?,
+            relative_offset_of_local_header: reader.read_u64::<LittleEndian>()?,
+            file_name: read_string(&mut reader)?,
+            file_name_bytes: read_bytes(&mut reader)?,
+            is_name_lossy: read_bool(&mut reader)?,
+            extra_field: read_bytes(&mut reader)?,
+            file_comment: read_bytes(&mut reader)?,
+            is_utf8: read_bool(&mut reader)?,
+            is_encrypted: read_bool(&mut reader)?,
+            is_zip64: read_bool(&mut reader)?,
+            aes: match read_bool(&mut reader)? {
+                true => Some(AesExtraField {
+                    vendor_version: reader.read_u16::<LittleEndian>()?,
+                    strength: reader.read_u8()?,
+                    compression_method: reader.read_u16::<LittleEndian>()?,
+                }),
+                false => None,
+            },
+        });
+    }
+    if reader.position() as usize != reader.get_ref().len() {
+        return Result::Err(SnapshotError::Corrupted);
+    }
+
+    Result::Ok(Snapshot {
+        eocd,
+        base_offset,
+        entries,
+        requested_encoding,
+        encoding,
+    })
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+// 長さ (u32) + バイト列
+fn put_bytes(buf: &mut Vec<u8>, value: &[u8]) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value);
+}
+
+fn read_bytes(reader: &mut io::Cursor<&[u8]>) -> Result<Vec<u8>, SnapshotError> {
+    let length = reader.read_u32::<LittleEndian>()? as usize;
+    // 残りより長い長さで大きな領域を確保しないようにする
+    let remaining = reader.get_ref().len() - reader.position() as usize;
+    if length > remaining {
+        return Result::Err(SnapshotError::Corrupted);
+    }
+    Result::Ok(ReadPodExt::read_exact(reader, length)?)
+}
+
+fn read_string(reader: &mut io::Cursor<&[u8]>) -> Result<String, SnapshotError> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| SnapshotError::Corrupted)
+}
+
+fn read_bool(reader: &mut io::Cursor<&[u8]>) -> Result<bool, SnapshotError> {
+    match reader.read_u8()? {
+        0 => Result::Ok(false),
+        1 => Result::Ok(true),
+        _ => Result::Err(SnapshotError::Corrupted),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::tests::create_cd_header;
+    use crate::zip::{parse_cd, COMPRESSION_METHOD_DEFLATED, COMPRESSION_METHOD_STORED};
+    use encoding_rs::SHIFT_JIS;
+
+    // Helper: Snapshot の内容をそのまま保存する
+    fn serialize_snapshot(snapshot: &Snapshot) -> Vec<u8> {
+        serialize(
+            &snapshot.eocd,
+            snapshot.base_offset,
+            &snapshot.entries,
+            snapshot.requested_encoding,
+            snapshot.encoding,
+        )
+    }
+
+    // Helper: 2 エントリのアーカイブを解析した状態を作る
+    fn create_snapshot() -> Snapshot {
+        let mut cd_data = Vec::new();
+        cd_data.extend(create_cd_header("readme.txt", 0, 10, 10, 0x12345678, COMPRESSION_METHOD_STORED, true));
+        // Shift_JIS の "日本語.txt"
+        let mut sjis = create_cd_header("______.txt", 100, 20, 40, 0x9abcdef0, COMPRESSION_METHOD_DEFLATED, false);
+        sjis[46..52].copy_from_slice(b"\x93\xfa\x96\x7b\x8c\xea");
+        cd_data.extend(sjis);
+        let cd_size = cd_data.len() as u64;
+        let (entries, encoding) = parse_cd(&mut io::Cursor::new(cd_data), 2, None).unwrap();
+        assert_eq!(entries[1].file_name, "日本語.txt");
+
+        Snapshot {
+            eocd: EOCD {
+                signature: 0x06054b50,
+                number_of_this_disk: 0,
+                number_of_disk_start_eocd: 0,
+                total_number_of_entries_on_disk: 2,
+                total_number_of_entries_in_cd: 2,
+                cd_size,
+                cd_offset: 200,
+                comment: b"license: CC-BY".to_vec(),
+                eocd_offset: 200 + cd_size,
+                eocd_size: 36,
+                is_zip64: false,
+            },
+            base_offset: 512,
+            entries,
+            requested_encoding: None,
+            encoding,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut snapshot = create_snapshot();
+        snapshot.entries[0].aes = Some(AesExtraField {
+            vendor_version: 2,
+            strength: 3,
+            compression_method: COMPRESSION_METHOD_DEFLATED,
+        });
+        let data = serialize_snapshot(&snapshot);
+        assert_eq!(data[..4], SNAPSHOT_MAGIC);
+
+        let restored = deserialize(&data).unwrap();
+        assert_eq!(restored.base_offset, 512);
+        assert_eq!(restored.requested_encoding, None);
+        assert_eq!(restored.encoding, LegacyEncoding::EncodingRs(SHIFT_JIS));
+        // EOCD と CDHeader は比較を実装していないので、全フィールドを出力した文字列で比較する
+        assert_eq!(format!("{:?}", restored.eocd), format!("{:?}", snapshot.eocd));
+        assert_eq!(format!("{:?}", restored.entries), format!("{:?}", snapshot.entries));
+
+        snapshot.requested_encoding = Some(LegacyEncoding::Cp437);
+        snapshot.entries.clear();
+        let restored = deserialize(&serialize_snapshot(&snapshot)).unwrap();
+        assert_eq!(restored.requested_encoding, Some(LegacyEncoding::Cp437));
+        assert!(restored.entries.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_invalid_data() {
+        let data = serialize_snapshot(&create_snapshot());
+
+        let mut wrong_magic = data.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(deserialize(&wrong_magic), Err(SnapshotError::InvalidMagic)));
+
+        let mut newer = data.clone();
+        newer[4..6].copy_from_slice(&2u16.to_le_bytes());
+        assert!(matches!(deserialize(&newer), Err(SnapshotError::UnsupportedVersion(2))));
+
+        // 本体の 1 バイトが壊れている
+        let mut flipped = data.clone();
+        flipped[40] ^= 0x01;
+        assert!(matches!(deserialize(&flipped), Err(SnapshotError::ChecksumMismatch)));
+
+        // 途中で切れている
+        assert!(matches!(deserialize(&data[..data.len() / 2]), Err(SnapshotError::ChecksumMismatch)));
+        assert!(matches!(deserialize(&data[..5]), Err(SnapshotError::Corrupted)));
+    }
+
+    #[test]
+    fn test_snapshot_rejects_inconsistent_body() {
+        // チェックサムは正しいが、末尾に余分なデータがある
+        let mut data = serialize_snapshot(&create_snapshot());
+        data.truncate(data.len() - CHECKSUM_LENGTH);
+        data.push(0);
+        let checksum = crc32fast::hash(&data);
+        data.extend_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(deserialize(&data), Err(SnapshotError::Corrupted)));
+    }
+}
@@ -0,0 +1,341 @@
+use crate::zip::{
+    CDHeader, LoadFileError, COMPRESSION_METHOD_DEFLATED, COMPRESSION_METHOD_STORED,
+    LFH_LENGTH, LFH_SIGNATURE,
+};
+#[cfg(feature = "deflate64")]
+use crate::zip::COMPRESSION_METHOD_DEFLATE64;
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+use podio::{LittleEndian, ReadPodExt};
+use std::cmp;
+use std::io;
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+enum Inflater {
+    Stored,
+    Deflated {
+        state: Box<InflateState>,
+        stream_end: bool,
+    },
+    #[cfg(feature = "deflate64")]
+    Deflate64(Box<deflate64::InflaterManaged>),
+}
+
+// ローカルヘッダから始まるエントリのデータを少しずつ受け取り、展開済みのデータを少しずつ返す
+pub struct StreamDecoder {
+    header: Vec<u8>,
+    skip: u64,
+    remaining: u64,
+    inflater: Inflater,
+    hasher: crc32fast::Hasher,
+    crc32: u32,
+    verify_crc: bool,
+    // ローカルヘッダと照合する CD の値
+    compression_method: u16,
+    file_name_length: usize,
+    is_encrypted: bool,
+}
+
+impl StreamDecoder {
+    pub fn new(cdh: &CDHeader, verify_crc: bool) -> Result<StreamDecoder, LoadFileError> {
+        let inflater = match cdh.compression_method {
+            COMPRESSION_METHOD_STORED => Inflater::Stored,
+            COMPRESSION_METHOD_DEFLATED => Inflater::Deflated {
+                state: InflateState::new_boxed(DataFormat::Raw),
+                stream_end: false,
+            },
+            #[cfg(feature = "deflate64")]
+            COMPRESSION_METHOD_DEFLATE64 => Inflater::Deflate64(Box::new(deflate64::InflaterManaged::new())),
+            method => return Result::Err(LoadFileError::UnsupportedCompressionMethod(method)),
+        };
+        Result::Ok(StreamDecoder {
+            header: Vec::with_capacity(LFH_LENGTH as usize),
+            skip: 0,
+            remaining: cdh.compressed_size,
+            inflater,
+            hasher: crc32fast::Hasher::new(),
+            crc32: cdh.crc32,
+            verify_crc,
+            compression_method: cdh.compression_method,
+            file_name_length: cdh.file_name_bytes.len(),
+            is_encrypted: cdh.is_encrypted,
+        })
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, LoadFileError> {
+        let data = self.consume_header(chunk)?;
+        // 圧縮データより後ろ（データディスクリプタなど）は捨てる
+        let length = cmp::min(self.remaining, data.len() as u64) as usize;
+        self.remaining -= length as u64;
+        let data = &data[..length];
+
+        let output = match &mut self.inflater {
+            Inflater::Stored => data.to_vec(),
+            Inflater::Deflated { state, stream_end } => {
+                let mut output = Vec::new();
+                inflate_chunk(state, stream_end, data, &mut output)?;
+                output
+            }
+            #[cfg(feature = "deflate64")]
+            Inflater::Deflate64(inflater) => inflate64_chunk(inflater, data)?,
+        };
+        self.hasher.update(&output);
+        Result::Ok(output)
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>, LoadFileError> {
+        if !self.is_header_consumed() || self.remaining > 0 {
+            return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let output = match &mut self.inflater {
+            Inflater::Stored => vec![],
+            Inflater::Deflated { state, stream_end } => {
+                let mut output = Vec::new();
+                inflate_chunk(state, stream_end, &[], &mut output)?;
+                if !*stream_end {
+                    return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+                }
+                output
+            }
+            #[cfg(feature = "deflate64")]
+            Inflater::Deflate64(inflater) => {
+                let output = inflate64_chunk(inflater, &[])?;
+                if !inflater.finished() {
+                    return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+                }
+                output
+            }
+        };
+        self.hasher.update(&output);
+
+        let actual = self.hasher.finalize();
+        if self.verify_crc && actual != self.crc32 {
+            return Result::Err(LoadFileError::CrcMismatch {
+                expected: self.crc32,
+                actual,
+            });
+        }
+        Result::Ok(output)
+    }
+
+    // ローカルヘッダの固定長部分が揃うまでバッファし、ファイル名と拡張フィールドは読み飛ばす。
+    // load_file と同じく、CD と食い違うローカルヘッダは UnmatchHeader とする。
+    // 戻り値はチャンクのうち圧縮データ以降の部分
+    fn consume_header<'a>(&mut self, chunk: &'a [u8]) -> Result<&'a [u8], LoadFileError> {
+        let mut chunk = chunk;
+        if (self.header.len() as u64) < LFH_LENGTH {
+            let length = cmp::min(LFH_LENGTH as usize - self.header.len(), chunk.len());
+            self.header.extend_from_slice(&chunk[..length]);
+            chunk = &chunk[length..];
+            if (self.header.len() as u64) < LFH_LENGTH {
+                return Result::Ok(&[]);
+            }
+            let mut reader = io::Cursor::new(&self.header);
+            if reader.read_u32::<LittleEndian>()? != LFH_SIGNATURE {
+                return Result::Err(LoadFileError::UnmatchHeader);
+            }
+            reader.set_position(6);
+            let general_purpose_bit_flag = reader.read_u16::<LittleEndian>()?;
+            let compression_method = reader.read_u16::<LittleEndian>()?;
+            reader.set_position(26);
+            let file_name_length = reader.read_u16::<LittleEndian>()?;
+            let extra_field_length = reader.read_u16::<LittleEndian>()?;
+            if compression_method != self.compression_method
+                || file_name_length as usize != self.file_name_length
+                || (general_purpose_bit_flag & 1 == 1) != self.is_encrypted
+            {
+                return Result::Err(LoadFileError::UnmatchHeader);
+            }
+            self.skip = file_name_length as u64 + extra_field_length as u64;
+        }
+        let skip = cmp::min(self.skip, chunk.len() as u64) as usize;
+        self.skip -= skip as u64;
+        Result::Ok(&chunk[skip..])
+    }
+
+    fn is_header_consumed(&self) -> bool {
+        self.header.len() as u64 == LFH_LENGTH && self.skip == 0
+    }
+}
+
+// 圧縮データ全体を output に展開する。load_file もエントリのストリームと同じ実装で展開する
+pub(crate) fn inflate_to_end(input: &[u8], output: &mut Vec<u8>) -> Result<(), LoadFileError> {
+    let mut state = InflateState::new_boxed(DataFormat::Raw);
+    let mut stream_end = false;
+    inflate_chunk(&mut state, &mut stream_end, input, output)?;
+    if !stream_end {
+        return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Result::Ok(())
+}
+
+// 渡された入力を展開できるところまで展開する。出力バッファが一杯になる間は繰り返す
+fn inflate_chunk(
+    state: &mut InflateState,
+    stream_end: &mut bool,
+    input: &[u8],
+    output: &mut Vec<u8>,
+) -> Result<(), LoadFileError> {
+    let mut input = input;
+    let mut buf = vec![0u8; OUTPUT_BUFFER_SIZE];
+    while !*stream_end {
+        let result = inflate(state, input, &mut buf, MZFlush::None);
+        input = &input[result.bytes_consumed..];
+        output.extend_from_slice(&buf[..result.bytes_written]);
+        match result.status {
+            Result::Ok(MZStatus::StreamEnd) => *stream_end = true,
+            Result::Ok(_) | Result::Err(MZError::Buf) => {
+                if result.bytes_consumed == 0 && result.bytes_written == 0 {
+                    break;
+                }
+            }
+            Result::Err(_) => {
+                return Result::Err(LoadFileError::IOError(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid deflate stream",
+                )))
+            }
+        }
+    }
+    Result::Ok(())
+}
+
+#[cfg(feature = "deflate64")]
+fn inflate64_chunk(
+    inflater: &mut deflate64::InflaterManaged,
+    input: &[u8],
+) -> Result<Vec<u8>, LoadFileError> {
+    let mut input = input;
+    let mut output = Vec::new();
+    let mut buf = vec![0u8; OUTPUT_BUFFER_SIZE];
+    while !inflater.finished() {
+        let result = inflater.inflate(input, &mut buf);
+        if result.data_error {
+            return Result::Err(LoadFileError::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid deflate64 stream",
+            )));
+        }
+        input = &input[result.bytes_consumed..];
+        output.extend_from_slice(&buf[..result.bytes_written]);
+        if result.bytes_consumed == 0 && result.bytes_written == 0 {
+            break;
+        }
+    }
+    Result::Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::tests::{create_cdh_for_test, create_local_file_header, create_local_file_header_deflated};
+    use miniz_oxide::deflate::compress_to_vec;
+
+    fn decode_in_chunks(decoder: &mut StreamDecoder, data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            output.extend(decoder.push(chunk).unwrap());
+        }
+        output
+    }
+
+    #[test]
+    fn test_stream_deflated_in_small_chunks() {
+        let original_data: Vec<u8> = (0..100000u32).map(|i| (i % 251) as u8).collect();
+        let crc32 = crc32fast::hash(&original_data);
+        let compressed_data = compress_to_vec(&original_data, 6);
+
+        let mut lfh = create_local_file_header_deflated("test.bin", &compressed_data, original_data.len() as u32, crc32);
+        // 範囲に含まれたデータディスクリプタ相当の余分なバイト
+        lfh.extend_from_slice(&[0xAA; 16]);
+        let cdh = create_cdh_for_test(
+            "test.bin",
+            compressed_data.len() as u64,
+            original_data.len() as u64,
+            crc32,
+            COMPRESSION_METHOD_DEFLATED,
+        );
+
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        let mut output = decode_in_chunks(&mut decoder, &lfh, 7);
+        output.extend(decoder.finish().unwrap());
+        assert_eq!(output, original_data);
+    }
+
+    #[test]
+    fn test_stream_stored() {
+        let crc32 = 0xF7D18982u32; // CRC32 of "Hello"
+        let lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, crc32, true);
+        let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        let mut output = decode_in_chunks(&mut decoder, &lfh, 3);
+        output.extend(decoder.finish().unwrap());
+        assert_eq!(output, b"Hello");
+    }
+
+    #[test]
+    fn test_stream_crc_mismatch() {
+        let lfh = create_local_file_header("test.txt", b"Hellp", COMPRESSION_METHOD_STORED, 0, true);
+        let cdh = create_cdh_for_test("test.txt", 5, 5, 0xF7D18982, COMPRESSION_METHOD_STORED);
+
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        decoder.push(&lfh).unwrap();
+        assert!(matches!(decoder.finish(), Err(LoadFileError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn test_stream_header_mismatch() {
+        let lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, 0, true);
+
+        // 圧縮方式が異なる
+        let cdh = create_cdh_for_test("test.txt", 5, 5, 0, COMPRESSION_METHOD_DEFLATED);
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        assert!(matches!(decoder.push(&lfh), Err(LoadFileError::UnmatchHeader)));
+
+        // ファイル名の長さが異なる
+        let cdh = create_cdh_for_test("other.txt", 5, 5, 0, COMPRESSION_METHOD_STORED);
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        assert!(matches!(decoder.push(&lfh), Err(LoadFileError::UnmatchHeader)));
+
+        // 暗号化フラグが異なる
+        let mut cdh = create_cdh_for_test("test.txt", 5, 5, 0, COMPRESSION_METHOD_STORED);
+        cdh.is_encrypted = true;
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        assert!(matches!(decoder.push(&lfh), Err(LoadFileError::UnmatchHeader)));
+    }
+
+    #[test]
+    fn test_stream_truncated() {
+        let lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, 0, true);
+        let cdh = create_cdh_for_test("test.txt", 5, 5, 0xF7D18982, COMPRESSION_METHOD_STORED);
+
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        decoder.push(&lfh[..lfh.len() - 1]).unwrap();
+        assert!(matches!(decoder.finish(), Err(LoadFileError::IOError(_))));
+    }
+
+    #[cfg(feature = "deflate64")]
+    #[test]
+    fn test_stream_deflate64() {
+        use crate::zip::tests::create_deflate64_fixture;
+
+        let (compressed_data, original_data) = create_deflate64_fixture();
+        let crc32 = crc32fast::hash(&original_data);
+        let mut lfh = create_local_file_header_deflated("test.bin", &compressed_data, original_data.len() as u32, crc32);
+        lfh[8..10].copy_from_slice(&COMPRESSION_METHOD_DEFLATE64.to_le_bytes());
+        let cdh = create_cdh_for_test(
+            "test.bin",
+            compressed_data.len() as u64,
+            original_data.len() as u64,
+            crc32,
+            COMPRESSION_METHOD_DEFLATE64,
+        );
+
+        let mut decoder = StreamDecoder::new(&cdh, true).unwrap();
+        let mut output = decode_in_chunks(&mut decoder, &lfh, 1000);
+        output.extend(decoder.finish().unwrap());
+        assert_eq!(output, original_data);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use crate::zip::LoadFileError;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::io;
+
+const PBKDF2_ITERATIONS: u32 = 1000;
+const PASSWORD_VERIFIER_LENGTH: usize = 2;
+const AUTHENTICATION_CODE_LENGTH: usize = 10;
+
+// WinZip の AES-CTR はカウンタを 1 から始めるリトルエンディアンの 128 ビット整数として扱う
+const INITIAL_COUNTER: [u8; 16] = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+fn key_length(strength: u8) -> Result<usize, LoadFileError> {
+    match strength {
+        1 => Result::Ok(16),
+        2 => Result::Ok(24),
+        3 => Result::Ok(32),
+        _ => Result::Err(LoadFileError::IOError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid AES strength",
+        ))),
+    }
+}
+
+fn apply_keystream(key: &[u8], data: &mut [u8]) {
+    match key.len() {
+        16 => ctr::Ctr128LE::<aes::Aes128>::new(key.into(), &INITIAL_COUNTER.into()).apply_keystream(data),
+        24 => ctr::Ctr128LE::<aes::Aes192>::new(key.into(), &INITIAL_COUNTER.into()).apply_keystream(data),
+        _ => ctr::Ctr128LE::<aes::Aes256>::new(key.into(), &INITIAL_COUNTER.into()).apply_keystream(data),
+    }
+}
+
+// 暗号化キー、認証キー、パスワード検証値を導出する
+fn derive_keys(password: &[u8], salt: &[u8], key_length: usize) -> Vec<u8> {
+    let mut derived = vec![0u8; key_length * 2 + PASSWORD_VERIFIER_LENGTH];
+    pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, PBKDF2_ITERATIONS, &mut derived);
+    derived
+}
+
+// ソルト、パスワード検証値、暗号化データ、認証コードの並びを復号し、暗号化データ部分を返す
+pub fn decrypt(data: &[u8], password: &[u8], strength: u8) -> Result<Vec<u8>, LoadFileError> {
+    let key_length = key_length(strength)?;
+    let salt_length = key_length / 2;
+    if data.len() < salt_length + PASSWORD_VERIFIER_LENGTH + AUTHENTICATION_CODE_LENGTH {
+        return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+    }
+    let (salt, rest) = data.split_at(salt_length);
+    let (verifier, rest) = rest.split_at(PASSWORD_VERIFIER_LENGTH);
+    let (encrypted, authentication_code) = rest.split_at(rest.len() - AUTHENTICATION_CODE_LENGTH);
+
+    let derived = derive_keys(password, salt, key_length);
+    if &derived[key_length * 2..] != verifier {
+        return Result::Err(LoadFileError::WrongPassword);
+    }
+
+    // 認証コードは暗号化データに対する HMAC-SHA1 の先頭 10 バイト
+    let mut mac = Hmac::<Sha1>::new_from_slice(&derived[key_length..key_length * 2])
+        .expect("HMAC can take key of any size");
+    mac.update(encrypted);
+    if mac.verify_truncated_left(authentication_code).is_err() {
+        return Result::Err(LoadFileError::AuthenticationFailed);
+    }
+
+    let mut buf = encrypted.to_vec();
+    apply_keystream(&derived[..key_length], &mut buf);
+    Result::Ok(buf)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Helper: WinZip AES 形式で暗号化する
+    pub(crate) fn encrypt(data: &[u8], password: &[u8], strength: u8) -> Vec<u8> {
+        let key_length = key_length(strength).unwrap();
+        let salt: Vec<u8> = (0..key_length / 2).map(|i| i as u8 * 7 + 3).collect();
+        let derived = derive_keys(password, &salt, key_length);
+
+        let mut encrypted = data.to_vec();
+        apply_keystream(&derived[..key_length], &mut encrypted);
+        let mut mac = Hmac::<Sha1>::new_from_slice(&derived[key_length..key_length * 2]).unwrap();
+        mac.update(&encrypted);
+
+        let mut result = salt;
+        result.extend_from_slice(&derived[key_length * 2..]);
+        result.extend_from_slice(&encrypted);
+        result.extend_from_slice(&mac.finalize().into_bytes()[..AUTHENTICATION_CODE_LENGTH]);
+        result
+    }
+
+    #[test]
+    fn test_decrypt_round_trip() {
+        // 複数ブロックにまたがる長さで各鍵長を確認する
+        let data: Vec<u8> = (0..100u8).collect();
+        for strength in 1..=3 {
+            let encrypted = encrypt(&data, b"secret", strength);
+            assert_eq!(decrypt(&encrypted, b"secret", strength).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_wrong_password() {
+        let encrypted = encrypt(b"Hello, AES!", b"secret", 3);
+        let result = decrypt(&encrypted, b"wrong", 3);
+        assert!(matches!(result, Err(LoadFileError::WrongPassword)));
+    }
+
+    #[test]
+    fn test_decrypt_tampered_data() {
+        let mut encrypted = encrypt(b"Hello, AES!", b"secret", 3);
+        encrypted[20] ^= 1;
+        let result = decrypt(&encrypted, b"secret", 3);
+        assert!(matches!(result, Err(LoadFileError::AuthenticationFailed)));
+    }
+}
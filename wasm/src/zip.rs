@@ -1,27 +1,47 @@
-use encoding_rs::SHIFT_JIS;
-use libflate;
+use crate::encoding::{self, LegacyEncoding};
+use crate::stream;
+use crate::winzip_aes;
+use crate::zipcrypto;
 use podio::{LittleEndian, ReadPodExt};
+use std::cmp;
 use std::io;
 use std::io::prelude::*;
 use std::string::FromUtf8Error;
 
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 pub struct EOCD {
     pub signature: u32,
-    pub number_of_this_disk: u16,
-    pub number_of_disk_start_eocd: u16,
-    pub total_number_of_entries_on_disk: u16,
-    pub total_number_of_entries_in_cd: u16,
-    pub cd_size: u32,
-    pub cd_offset: u32,
+    pub number_of_this_disk: u32,
+    pub number_of_disk_start_eocd: u32,
+    pub total_number_of_entries_on_disk: u64,
+    pub total_number_of_entries_in_cd: u64,
+    pub cd_size: u64,
+    pub cd_offset: u64,
     pub comment: Vec<u8>,
 
-    pub eocd_offset: u32,
-    pub eocd_size: u32,
+    // ZIP64 の場合は ZIP64 EOCD レコードの先頭から EOCD の終端までを指す
+    pub eocd_offset: u64,
+    pub eocd_size: u64,
+    pub is_zip64: bool,
 }
 
-#[derive(Debug)]
+impl EOCD {
+    // 先頭に別のデータ（自己解凍形式のスタブなど）が付いていると、書かれているオフセットは実際の位置より前を指す。
+    // CD は EOCD（ZIP64 の場合は ZIP64 EOCD レコード）の直前で終わるので、その差を補正値として返す。
+    // data_offset は parse_eocd に渡したデータのファイル先頭からの位置
+    pub fn base_offset(&self, data_offset: u64) -> u64 {
+        let cd_end = self.cd_offset.saturating_add(self.cd_size);
+        (data_offset + self.eocd_offset).saturating_sub(cd_end)
+    }
+
+    // アーカイブのコメントには UTF-8 フラグがないため、ファイル名と同じエンコーディングで変換する
+    pub fn decoded_comment(&self, encoding: LegacyEncoding) -> String {
+        decode_lossy(&self.comment, false, encoding)
+    }
+}
+
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct CDHeader {
     pub signature: u32,
@@ -32,108 +52,399 @@ pub struct CDHeader {
     pub last_mod_file_time: u16,
     pub last_mod_file_date: u16,
     pub crc32: u32,
-    pub compressed_size: u32,
-    pub uncompressed_size: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
     pub file_name_length: u16,
     pub extra_field_length: u16,
     pub file_comment_length: u16,
-    pub disk_number_start: u16,
+    pub disk_number_start: u32,
     pub internal_file_attributes: u16,
     pub external_file_attributes: u32,
-    pub relative_offset_of_local_header: u32,
+    pub relative_offset_of_local_header: u64,
 
     pub file_name: String,
+    pub file_name_bytes: Vec<u8>,
+    // file_name を厳密に変換できず、変換できない部分を U+FFFD に置き換えた
+    pub is_name_lossy: bool,
     pub extra_field: Vec<u8>,
     pub file_comment: Vec<u8>,
 
     pub is_utf8: bool,
     pub is_encrypted: bool,
+    pub is_zip64: bool,
+    pub aes: Option<AesExtraField>,
+}
+
+// WinZip AES 拡張フィールド (0x9901)
+#[derive(Debug, Clone, Copy)]
+pub struct AesExtraField {
+    // 1: AE-1, 2: AE-2（AE-2 は CRC を持たない）
+    pub vendor_version: u16,
+    // 1: AES-128, 2: AES-192, 3: AES-256
+    pub strength: u8,
+    pub compression_method: u16,
+}
+
+// Unix エポックからのミリ秒で表したエントリの日時
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timestamps {
+    pub modified: Option<i64>,
+    pub accessed: Option<i64>,
+    pub created: Option<i64>,
+}
+
+impl CDHeader {
+    // version made by の上位バイト。0: MS-DOS, 3: Unix, 10: NTFS, 19: OS X など
+    pub fn host_os(&self) -> u8 {
+        (self.version_made_by >> 8) as u8
+    }
+
+    // Unix 系で作成された場合は外部属性の上位 16 ビットに st_mode が入る
+    pub fn unix_mode(&self) -> Option<u32> {
+        match self.host_os() {
+            HOST_OS_UNIX | HOST_OS_OSX => match self.external_file_attributes >> 16 {
+                0 => None,
+                mode => Some(mode),
+            },
+            _ => None,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        if self.file_name.ends_with('/') {
+            return true;
+        }
+        match self.unix_mode() {
+            Some(mode) => mode & S_IFMT == S_IFDIR,
+            // MS-DOS の属性は外部属性の下位バイトに入る
+            None => self.external_file_attributes & DOS_ATTRIBUTE_DIRECTORY != 0,
+        }
+    }
+
+    // シンボリックリンクの場合、エントリのデータがリンク先のパスになる
+    pub fn is_symlink(&self) -> bool {
+        matches!(self.unix_mode(), Some(mode) if mode & S_IFMT == S_IFLNK)
+    }
+
+    // 拡張フィールドの日時を NTFS、拡張タイムスタンプの順に優先する。
+    // どちらもなければ更新日時だけ MS-DOS 形式の日時から求める
+    pub fn timestamps(&self) -> Timestamps {
+        // NTFS の値が 0 のものは日時なしなので、項目ごとに拡張タイムスタンプ、DOS の日時の順で補う
+        let ntfs = find_extra_field(&self.extra_field, EXTRA_FIELD_NTFS)
+            .and_then(parse_ntfs_extra_field)
+            .unwrap_or_default();
+        let extended = find_extra_field(&self.extra_field, EXTRA_FIELD_EXTENDED_TIMESTAMP)
+            .map(parse_extended_timestamp_extra_field)
+            .unwrap_or_default();
+        Timestamps {
+            modified: ntfs
+                .modified
+                .or(extended.modified)
+                .or_else(|| dos_datetime_to_unix_millis(self.last_mod_file_date, self.last_mod_file_time)),
+            accessed: ntfs.accessed.or(extended.accessed),
+            created: ntfs.created.or(extended.created),
+        }
+    }
+
+    pub fn decoded_comment(&self, encoding: LegacyEncoding) -> String {
+        match self.unicode_comment() {
+            Some(comment) => comment,
+            None => decode_lossy(&self.file_comment, self.is_utf8, encoding),
+        }
+    }
+
+    // Info-ZIP Unicode Path 拡張フィールドに格納された UTF-8 の名前
+    pub fn unicode_path(&self) -> Option<String> {
+        if self.is_utf8 {
+            return None;
+        }
+        find_unicode_extra_field(&self.extra_field, EXTRA_FIELD_UNICODE_PATH, &self.file_name_bytes)
+    }
+
+    // Info-ZIP Unicode Comment 拡張フィールドに格納された UTF-8 のコメント
+    pub fn unicode_comment(&self) -> Option<String> {
+        if self.is_utf8 {
+            return None;
+        }
+        find_unicode_extra_field(&self.extra_field, EXTRA_FIELD_UNICODE_COMMENT, &self.file_comment)
+    }
 }
 
 pub const LFH_SIGNATURE: u32 = 0x04034b50;
 pub const CD_SIGNATURE: u32 = 0x02014b50;
 pub const EOCD_SIGNATURE: u32 = 0x06054b50;
-// pub const ZIP64_EOCD_SIGNATURE : u32 = 0x06064b50;
-// const ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE : u32 = 0x07064b50;
+pub const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+pub const ZIP64_EOCD_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+pub const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+
+pub const EOCD_LENGTH: u64 = 22;
+pub const ZIP64_EOCD_LENGTH: u64 = 56;
+pub const ZIP64_EOCD_LOCATOR_LENGTH: u64 = 20;
+pub const LFH_LENGTH: u64 = 30;
+pub const CD_MIN_LENGTH: u64 = 46;
+
+// EOCD の後ろにはコメント（最大 65535 バイト）しか置けないため、末尾からこの長さの範囲だけ探す
+pub const MAX_EOCD_SEARCH_LENGTH: u64 = EOCD_LENGTH + 0xFFFF;
+
+// ZIP64 EOCD レコードがロケータの示す位置にない場合に、ロケータの手前から探す長さ
+pub const MAX_ZIP64_EOCD_SEARCH_LENGTH: u64 = 0x10000;
+
+// ローカルヘッダの拡張フィールドは CD と長さが異なることがあるため、これだけ余分に見込む
+pub const DEFAULT_EXTRA_FIELD_ALLOWANCE: u64 = 64;
+
+pub const HOST_OS_UNIX: u8 = 3;
+pub const HOST_OS_OSX: u8 = 19;
+
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const DOS_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+
+pub const EXTRA_FIELD_ZIP64: u16 = 0x0001;
+pub const EXTRA_FIELD_NTFS: u16 = 0x000a;
+pub const EXTRA_FIELD_EXTENDED_TIMESTAMP: u16 = 0x5455;
+pub const EXTRA_FIELD_AES: u16 = 0x9901;
+pub const EXTRA_FIELD_UNICODE_COMMENT: u16 = 0x6375;
+pub const EXTRA_FIELD_UNICODE_PATH: u16 = 0x7075;
+
+// 展開先を先に確保する量の上限。これを超える場合は展開しながら伸ばす
+const MAX_CAPACITY_HINT: u64 = 64 * 1024 * 1024;
+const MAX_EXPECTED_COMPRESSION_RATIO: u64 = 32;
 
 pub const COMPRESSION_METHOD_STORED: u16 = 0;
 pub const COMPRESSION_METHOD_DEFLATED: u16 = 8;
+pub const COMPRESSION_METHOD_AES: u16 = 99;
+#[cfg(feature = "deflate64")]
+pub const COMPRESSION_METHOD_DEFLATE64: u16 = 9;
+#[cfg(feature = "bzip2")]
+pub const COMPRESSION_METHOD_BZIP2: u16 = 12;
+#[cfg(feature = "lzma")]
+pub const COMPRESSION_METHOD_LZMA: u16 = 14;
+#[cfg(feature = "zstd")]
+pub const COMPRESSION_METHOD_ZSTD: u16 = 93;
+#[cfg(feature = "xz")]
+pub const COMPRESSION_METHOD_XZ: u16 = 95;
 
 #[derive(Debug)]
 pub enum ParseEOCDError {
     TooShortDataLength,
+    // 探索範囲に EOCD のシグネチャが見つからない
     InvalidSignature,
+    // シグネチャは見つかったが、コメント長がデータの終端と合わない
+    CommentLengthMismatch,
+    // シグネチャは見つかったが、CD のサイズや位置があり得ない値になっている
+    ImplausibleRecord,
+    Zip64RecordNotFound,
     IOError(io::Error),
 }
 
 #[derive(Debug)]
 pub enum ParseCDError {
     InvalidSignature,
-    FileNameConversionError,
     IOError(io::Error),
 }
 
 #[derive(Debug)]
 pub enum LoadFileError {
     InvalidSignature,
-    FileNameConversionError,
     UnmatchHeader,
     UnsupportedCompressionMethod(u16),
+    CrcMismatch { expected: u32, actual: u32 },
+    PasswordRequired,
+    WrongPassword,
+    AuthenticationFailed,
+    // 圧縮方式が WinZip AES (99) なのに、AES 拡張フィールドが無いか壊れている
+    InvalidAesExtraField,
+    // 渡された断片がエントリ全体を含んでいない
+    ChunkNotCovered,
     IOError(io::Error),
 }
 
 #[derive(Debug)]
 enum FileNameError {
     FromUtf8Error,
-    FromSJISError,
+    FromLegacyEncodingError,
 }
 
-pub fn parse_eocd(cursor: &mut io::Cursor<Vec<u8>>) -> Result<EOCD, ParseEOCDError> {
+// data_offset は渡したデータのファイル先頭からの位置。分かっている場合は CD の位置の検証に使う
+pub fn parse_eocd(cursor: &mut io::Cursor<Vec<u8>>, data_offset: Option<u64>) -> Result<EOCD, ParseEOCDError> {
     let length = cursor.seek(io::SeekFrom::End(0))?;
-    if length < 22 {
+    if length < EOCD_LENGTH {
         return Result::Err(ParseEOCDError::TooShortDataLength);
     }
-    let mut signature: u32;
-    cursor.seek(io::SeekFrom::End(-22))?;
-    loop {
-        signature = cursor.read_u32::<LittleEndian>()?;
-        if signature == EOCD_SIGNATURE {
-            break;
-        }
-        cursor.seek(io::SeekFrom::Current(-5))?;
-    }
-    let eocd_offset = cursor.position() as u32 - 4;
+    let mut eocd_offset = find_eocd(cursor.get_ref(), data_offset)?;
+    cursor.set_position(eocd_offset);
+    let signature = cursor.read_u32::<LittleEndian>()?;
 
-    let number_of_this_disk = cursor.read_u16::<LittleEndian>()?;
-    let number_of_disk_start_eocd = cursor.read_u16::<LittleEndian>()?;
-    let total_number_of_entries_on_disk = cursor.read_u16::<LittleEndian>()?;
-    let total_number_of_entries_in_cd = cursor.read_u16::<LittleEndian>()?;
-    let cd_size = cursor.read_u32::<LittleEndian>()?;
-    let cd_offset = cursor.read_u32::<LittleEndian>()?;
+    let mut number_of_this_disk = cursor.read_u16::<LittleEndian>()? as u32;
+    let mut number_of_disk_start_eocd = cursor.read_u16::<LittleEndian>()? as u32;
+    let mut total_number_of_entries_on_disk = cursor.read_u16::<LittleEndian>()? as u64;
+    let mut total_number_of_entries_in_cd = cursor.read_u16::<LittleEndian>()? as u64;
+    let mut cd_size = cursor.read_u32::<LittleEndian>()? as u64;
+    let mut cd_offset = cursor.read_u32::<LittleEndian>()? as u64;
     let comment_length = cursor.read_u16::<LittleEndian>()? as usize;
     let comment = ReadPodExt::read_exact(cursor, comment_length)?;
 
-    let eocd_size = 4 + 2 + 2 + 2 + 2 + 4 + 4 + 2 + comment_length as u32;
+    let eocd_end = cursor.position();
+
+    // ZIP64 EOCD ロケータは EOCD の直前に置かれる
+    let has_locator = eocd_offset >= ZIP64_EOCD_LOCATOR_LENGTH && {
+        cursor.set_position(eocd_offset - ZIP64_EOCD_LOCATOR_LENGTH);
+        cursor.read_u32::<LittleEndian>()? == ZIP64_EOCD_LOCATOR_SIGNATURE
+    };
+    let needs_zip64 = total_number_of_entries_on_disk == 0xFFFF
+        || total_number_of_entries_in_cd == 0xFFFF
+        || cd_size == 0xFFFFFFFF
+        || cd_offset == 0xFFFFFFFF;
+    if needs_zip64 && !has_locator {
+        return Result::Err(ParseEOCDError::Zip64RecordNotFound);
+    }
+
+    if has_locator {
+        let locator_offset = eocd_offset - ZIP64_EOCD_LOCATOR_LENGTH;
+        let record_offset = find_zip64_eocd_record(cursor, locator_offset, data_offset)?;
+        cursor.set_position(record_offset + 4 + 8 + 2 + 2);
+        number_of_this_disk = cursor.read_u32::<LittleEndian>()?;
+        number_of_disk_start_eocd = cursor.read_u32::<LittleEndian>()?;
+        total_number_of_entries_on_disk = cursor.read_u64::<LittleEndian>()?;
+        total_number_of_entries_in_cd = cursor.read_u64::<LittleEndian>()?;
+        cd_size = cursor.read_u64::<LittleEndian>()?;
+        cd_offset = cursor.read_u64::<LittleEndian>()?;
+        eocd_offset = record_offset;
+    }
 
     let eocd = EOCD {
-        signature: signature,
-        number_of_this_disk: number_of_this_disk,
-        number_of_disk_start_eocd: number_of_disk_start_eocd,
-        total_number_of_entries_on_disk: total_number_of_entries_on_disk,
-        total_number_of_entries_in_cd: total_number_of_entries_in_cd,
-        cd_size: cd_size,
-        cd_offset: cd_offset,
-        comment: comment,
-        eocd_offset: eocd_offset,
-        eocd_size: eocd_size,
+        signature,
+        number_of_this_disk,
+        number_of_disk_start_eocd,
+        total_number_of_entries_on_disk,
+        total_number_of_entries_in_cd,
+        cd_size,
+        cd_offset,
+        comment,
+        eocd_offset,
+        eocd_size: eocd_end - eocd_offset,
+        is_zip64: has_locator,
     };
     Result::Ok(eocd)
 }
 
+// 末尾から前に向かって EOCD のシグネチャを探し、検証に通った最初の位置を返す。
+// コメントの中に偶然シグネチャが含まれていても、コメント長や CD のサイズ・位置が合わないものは読み飛ばす
+fn find_eocd(data: &[u8], data_offset: Option<u64>) -> Result<u64, ParseEOCDError> {
+    let length = data.len() as u64;
+    let lower = length.saturating_sub(MAX_EOCD_SEARCH_LENGTH);
+    let mut error = ParseEOCDError::InvalidSignature;
+    let mut position = length - EOCD_LENGTH;
+    loop {
+        let start = position as usize;
+        if data[start..start + 4] == EOCD_SIGNATURE.to_le_bytes() {
+            let absolute_position = data_offset.and_then(|offset| offset.checked_add(position));
+            match validate_eocd(&data[start..], absolute_position) {
+                Result::Ok(()) => return Result::Ok(position),
+                // 末尾に最も近い候補の理由を返す
+                Result::Err(err) => {
+                    if matches!(error, ParseEOCDError::InvalidSignature) {
+                        error = err;
+                    }
+                }
+            }
+        }
+        if position == lower {
+            return Result::Err(error);
+        }
+        position -= 1;
+    }
+}
+
+// record は EOCD の先頭からデータの終端まで。position は分かっていればその EOCD のファイル上の位置
+fn validate_eocd(record: &[u8], position: Option<u64>) -> Result<(), ParseEOCDError> {
+    let u16_at = |i: usize| u16::from_le_bytes([record[i], record[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+
+    let comment_length = u16_at(20) as usize;
+    if record.len() != EOCD_LENGTH as usize + comment_length {
+        return Result::Err(ParseEOCDError::CommentLengthMismatch);
+    }
+
+    let number_of_this_disk = u16_at(4);
+    let number_of_disk_start_eocd = u16_at(6);
+    let entries_on_disk = u16_at(8);
+    let entries_in_cd = u16_at(10);
+    let cd_size = u32_at(12);
+    let cd_offset = u32_at(16);
+    // ZIP64 の場合、実際の値は ZIP64 EOCD レコードにある
+    if entries_on_disk == 0xFFFF || entries_in_cd == 0xFFFF || cd_size == 0xFFFFFFFF || cd_offset == 0xFFFFFFFF {
+        return Result::Ok(());
+    }
+    if number_of_disk_start_eocd > number_of_this_disk
+        || entries_on_disk > entries_in_cd
+        || (cd_size as u64) < entries_in_cd as u64 * CD_MIN_LENGTH
+    {
+        return Result::Err(ParseEOCDError::ImplausibleRecord);
+    }
+    // CD は EOCD より前で終わる。先頭にデータが付いていても書かれたオフセットは実際の位置より小さくなるだけ
+    if let Some(position) = position {
+        if (cd_offset as u64).checked_add(cd_size as u64).is_none_or(|cd_end| cd_end > position) {
+            return Result::Err(ParseEOCDError::ImplausibleRecord);
+        }
+    }
+    Result::Ok(())
+}
+
+// ロケータが持つ ZIP64 EOCD レコードのオフセットはアーカイブ先頭からの位置なので、
+// data_offset で渡されたバッファ内の位置に直してから確かめる。
+// 先頭に別のデータが付いているとずれるため、見つからなければロケータの直前から一定の範囲だけ遡って探す
+fn find_zip64_eocd_record(
+    cursor: &mut io::Cursor<Vec<u8>>,
+    locator_offset: u64,
+    data_offset: Option<u64>,
+) -> Result<u64, ParseEOCDError> {
+    cursor.set_position(locator_offset + 8);
+    let stored_offset = cursor.read_u64::<LittleEndian>()?;
+    if let Some(record_offset) = stored_offset.checked_sub(data_offset.unwrap_or(0)) {
+        if is_zip64_eocd_record(cursor, record_offset, locator_offset)? {
+            return Result::Ok(record_offset);
+        }
+    }
+
+    let upper = match locator_offset.checked_sub(ZIP64_EOCD_LENGTH) {
+        Some(offset) => offset,
+        None => return Result::Err(ParseEOCDError::Zip64RecordNotFound),
+    };
+    let lower = upper.saturating_sub(MAX_ZIP64_EOCD_SEARCH_LENGTH);
+    for record_offset in (lower..=upper).rev() {
+        if is_zip64_eocd_record(cursor, record_offset, locator_offset)? {
+            return Result::Ok(record_offset);
+        }
+    }
+    Result::Err(ParseEOCDError::Zip64RecordNotFound)
+}
+
+// レコードはロケータの直前で終わっていなければならない
+fn is_zip64_eocd_record(
+    cursor: &mut io::Cursor<Vec<u8>>,
+    record_offset: u64,
+    locator_offset: u64,
+) -> Result<bool, ParseEOCDError> {
+    if record_offset.checked_add(ZIP64_EOCD_LENGTH).is_none_or(|end| end > locator_offset) {
+        return Result::Ok(false);
+    }
+    cursor.set_position(record_offset);
+    if cursor.read_u32::<LittleEndian>()? != ZIP64_EOCD_SIGNATURE {
+        return Result::Ok(false);
+    }
+    // size of zip64 end of central directory record は先頭 12 バイトを含まない
+    let size_of_record = cursor.read_u64::<LittleEndian>()?;
+    Result::Ok(record_offset.checked_add(12).and_then(|n| n.checked_add(size_of_record)) == Some(locator_offset))
+}
+
+// encoding が None の場合は UTF-8 フラグのない名前全体から推定する。実際に使ったエンコーディングも返す
 pub fn parse_cd(
     cursor: &mut io::Cursor<Vec<u8>>,
     count: usize,
-) -> Result<Vec<CDHeader>, ParseCDError> {
+    encoding: Option<LegacyEncoding>,
+) -> Result<(Vec<CDHeader>, LegacyEncoding), ParseCDError> {
     let mut cdhs: Vec<CDHeader> = Vec::with_capacity(count);
     while cdhs.len() < count {
         let signature = cursor.read_u32::<LittleEndian>()?;
@@ -148,15 +459,15 @@ pub fn parse_cd(
         let last_mod_file_time = cursor.read_u16::<LittleEndian>()?;
         let last_mod_file_date = cursor.read_u16::<LittleEndian>()?;
         let crc32 = cursor.read_u32::<LittleEndian>()?;
-        let compressed_size = cursor.read_u32::<LittleEndian>()?;
-        let uncompressed_size = cursor.read_u32::<LittleEndian>()?;
+        let mut compressed_size = cursor.read_u32::<LittleEndian>()? as u64;
+        let mut uncompressed_size = cursor.read_u32::<LittleEndian>()? as u64;
         let file_name_length = cursor.read_u16::<LittleEndian>()?;
         let extra_field_length = cursor.read_u16::<LittleEndian>()?;
         let file_comment_length = cursor.read_u16::<LittleEndian>()?;
-        let disk_number_start = cursor.read_u16::<LittleEndian>()?;
+        let mut disk_number_start = cursor.read_u16::<LittleEndian>()? as u32;
         let internal_file_attributes = cursor.read_u16::<LittleEndian>()?;
         let external_file_attributes = cursor.read_u32::<LittleEndian>()?;
-        let relative_offset_of_local_header = cursor.read_u32::<LittleEndian>()?;
+        let mut relative_offset_of_local_header = cursor.read_u32::<LittleEndian>()? as u64;
         let file_name_bytes = ReadPodExt::read_exact(cursor, file_name_length as usize)?;
         let extra_field = ReadPodExt::read_exact(cursor, extra_field_length as usize)?;
         let file_comment = ReadPodExt::read_exact(cursor, file_comment_length as usize)?;
@@ -164,41 +475,93 @@ pub fn parse_cd(
         let is_utf8 = general_purpose_bit_flag & (1 << 11) != 0;
         let is_encrypted = general_purpose_bit_flag & 1 == 1;
 
-        let file_name = decode_file_name(&file_name_bytes, is_utf8)?;
+        // ZIP64 拡張情報には 0xFFFFFFFF / 0xFFFF になっている項目だけがこの順で格納される
+        let zip64_field = find_extra_field(&extra_field, EXTRA_FIELD_ZIP64);
+        let is_zip64 = zip64_field.is_some();
+        if let Some(field) = zip64_field {
+            let mut reader = io::Cursor::new(field);
+            if uncompressed_size == 0xFFFFFFFF {
+                uncompressed_size = reader.read_u64::<LittleEndian>()?;
+            }
+            if compressed_size == 0xFFFFFFFF {
+                compressed_size = reader.read_u64::<LittleEndian>()?;
+            }
+            if relative_offset_of_local_header == 0xFFFFFFFF {
+                relative_offset_of_local_header = reader.read_u64::<LittleEndian>()?;
+            }
+            if disk_number_start == 0xFFFF {
+                disk_number_start = reader.read_u32::<LittleEndian>()?;
+            }
+        }
+
+        // 壊れた AES 拡張フィールドは無いものとして扱い、そのエントリだけを読めなくする
+        let aes = find_extra_field(&extra_field, EXTRA_FIELD_AES).and_then(|field| parse_aes_extra_field(field).ok());
 
         let cdh = CDHeader {
-            signature: signature,
-            version_made_by: version_made_by,
-            version_needed_to_extract: version_needed_to_extract,
-            general_purpose_bit_flag: general_purpose_bit_flag,
-            compression_method: compression_method,
-            last_mod_file_time: last_mod_file_time,
-            last_mod_file_date: last_mod_file_date,
-            crc32: crc32,
-            compressed_size: compressed_size,
-            uncompressed_size: uncompressed_size,
-            file_name_length: file_name_length,
-            extra_field_length: extra_field_length,
-            file_comment_length: file_comment_length,
-            disk_number_start: disk_number_start,
-            internal_file_attributes: internal_file_attributes,
-            external_file_attributes: external_file_attributes,
-            relative_offset_of_local_header: relative_offset_of_local_header,
-            file_name: file_name,
-            extra_field: extra_field,
-            file_comment: file_comment,
-            is_utf8: is_utf8,
-            is_encrypted: is_encrypted,
+            signature,
+            version_made_by,
+            version_needed_to_extract,
+            general_purpose_bit_flag,
+            compression_method,
+            last_mod_file_time,
+            last_mod_file_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            file_name_length,
+            extra_field_length,
+            file_comment_length,
+            disk_number_start,
+            internal_file_attributes,
+            external_file_attributes,
+            relative_offset_of_local_header,
+            // エンコーディングが決まってから変換する
+            file_name: String::new(),
+            file_name_bytes,
+            is_name_lossy: false,
+            extra_field,
+            file_comment,
+            is_utf8,
+            is_encrypted,
+            is_zip64,
+            aes,
         };
         cdhs.push(cdh);
     }
 
-    Result::Ok(cdhs)
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => {
+            let names: Vec<&[u8]> = cdhs
+                .iter()
+                .filter(|cdh| !cdh.is_utf8 && cdh.unicode_path().is_none())
+                .map(|cdh| cdh.file_name_bytes.as_slice())
+                .collect();
+            encoding::detect(&names)
+        }
+    };
+    // 変換できない名前が 1 つあってもアーカイブ全体を読めなくしないよう、置き換えて続ける
+    for cdh in cdhs.iter_mut() {
+        cdh.file_name = match cdh.unicode_path() {
+            Some(file_name) => file_name,
+            None => match decode_file_name(&cdh.file_name_bytes, cdh.is_utf8, encoding) {
+                Result::Ok(file_name) => file_name,
+                Result::Err(_) => {
+                    cdh.is_name_lossy = true;
+                    decode_lossy(&cdh.file_name_bytes, cdh.is_utf8, encoding)
+                }
+            },
+        };
+    }
+
+    Result::Ok((cdhs, encoding))
 }
 
 pub fn load_file(
     mut cursor: io::Cursor<Vec<u8>>,
     cdh: &CDHeader,
+    verify_crc: bool,
+    password: Option<&[u8]>,
 ) -> Result<Vec<u8>, LoadFileError> {
     let signature = cursor.read_u32::<LittleEndian>()?;
     if signature != LFH_SIGNATURE {
@@ -210,37 +573,57 @@ pub fn load_file(
 
     cursor.seek(io::SeekFrom::Current(4))?;
     let mut crc32 = cursor.read_u32::<LittleEndian>()?;
-    let mut compressed_size = cursor.read_u32::<LittleEndian>()?;
-    let mut uncompressed_size = cursor.read_u32::<LittleEndian>()?;
+    let mut compressed_size = cursor.read_u32::<LittleEndian>()? as u64;
+    let mut uncompressed_size = cursor.read_u32::<LittleEndian>()? as u64;
 
     let file_name_length = cursor.read_u16::<LittleEndian>()?;
     let extra_field_length = cursor.read_u16::<LittleEndian>()?;
     let file_name_bytes = ReadPodExt::read_exact(&mut cursor, file_name_length as usize)?;
-    cursor.seek(io::SeekFrom::Current(extra_field_length as i64))?;
+    let extra_field = ReadPodExt::read_exact(&mut cursor, extra_field_length as usize)?;
 
     let is_encrypted = general_purpose_bit_flag & 1 == 1;
-    let is_utf8 = general_purpose_bit_flag & (1 << 11) != 0;
     let use_fd = general_purpose_bit_flag & (1 << 3) != 0;
 
-    let file_name = decode_file_name(&file_name_bytes, is_utf8)?;
+    let zip64_field = find_extra_field(&extra_field, EXTRA_FIELD_ZIP64);
+    let is_zip64 = zip64_field.is_some() || cdh.is_zip64;
+    if let Some(field) = zip64_field {
+        let mut reader = io::Cursor::new(field);
+        if uncompressed_size == 0xFFFFFFFF {
+            uncompressed_size = reader.read_u64::<LittleEndian>()?;
+        }
+        if compressed_size == 0xFFFFFFFF {
+            compressed_size = reader.read_u64::<LittleEndian>()?;
+        }
+    }
 
+    // データディスクリプタはデータの直後にある。範囲の末尾には余分なバイトが含まれうるので
+    // バッファ終端からではなく CD の圧縮サイズから位置を求める
     if use_fd {
-        let position = cursor.position();
-        cursor.seek(io::SeekFrom::End(-12))?;
-        crc32 = cursor.read_u32::<LittleEndian>()?;
-        compressed_size = cursor.read_u32::<LittleEndian>()?;
-        uncompressed_size = cursor.read_u32::<LittleEndian>()?;
-        cursor.set_position(position);
+        let position = cursor.position() + cdh.compressed_size;
+        let rest = cursor.get_ref().get(position as usize..).unwrap_or(&[]);
+        let descriptor = match parse_data_descriptor(rest, cdh, is_zip64) {
+            Some(descriptor) => descriptor,
+            // 範囲がディスクリプタの手前で終わっている場合は CD の値を使う
+            None => DataDescriptor {
+                crc32: cdh.crc32,
+                compressed_size: cdh.compressed_size,
+                uncompressed_size: cdh.uncompressed_size,
+            },
+        };
+        crc32 = descriptor.crc32;
+        compressed_size = descriptor.compressed_size;
+        uncompressed_size = descriptor.uncompressed_size;
     }
 
-    if file_name != cdh.file_name
+    // 名前はエンコーディングに依存しないようバイト列のまま比較する
+    if file_name_bytes != cdh.file_name_bytes
         || crc32 != cdh.crc32
         || is_encrypted != cdh.is_encrypted
         || compressed_size != cdh.compressed_size
         || uncompressed_size != cdh.uncompressed_size
     {
         console_log!("crc32: {} vs {}", crc32, cdh.crc32);
-        console_log!("file_name: {} vs {}", file_name, cdh.file_name);
+        console_log!("file_name: {} vs {}", String::from_utf8_lossy(&file_name_bytes), cdh.file_name);
         console_log!("is_encrypted: {} vs {}", is_encrypted, cdh.is_encrypted);
         console_log!("compressed_size: {} vs {}", compressed_size, cdh.compressed_size);
         console_log!("uncompressed_size: {} vs {}", uncompressed_size, cdh.uncompressed_size);
@@ -248,35 +631,448 @@ pub fn load_file(
         return Result::Err(LoadFileError::UnmatchHeader);
     }
 
+    // 途中で切れた範囲を渡されてもパニックせず、エラーとして返す
     let start = cursor.position() as usize;
-    let end = start + compressed_size as usize;
+    let end = usize::try_from(compressed_size)
+        .ok()
+        .and_then(|size| start.checked_add(size))
+        .ok_or(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()))?;
     let data = cursor.into_inner();
-    let data = data[start..end].to_vec();
+    let data = data
+        .get(start..end)
+        .ok_or(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()))?
+        .to_vec();
+
+    // 復号してから展開する。WinZip AES の場合は本来の圧縮方式が拡張フィールドに入っている
+    let mut verify_crc = verify_crc;
+    let (data, compression_method) = match cdh.aes {
+        Some(aes) if compression_method == COMPRESSION_METHOD_AES => {
+            let password = password.ok_or(LoadFileError::PasswordRequired)?;
+            // AE-2 は CRC の代わりに認証コードで検証する
+            if aes.vendor_version == 2 {
+                verify_crc = false;
+            }
+            (winzip_aes::decrypt(&data, password, aes.strength)?, aes.compression_method)
+        }
+        // ZipCrypto として復号すると WrongPassword になり原因が分からないので、専用のエラーを返す
+        None if is_encrypted && compression_method == COMPRESSION_METHOD_AES => {
+            return Result::Err(LoadFileError::InvalidAesExtraField);
+        }
+        _ if is_encrypted => {
+            let password = password.ok_or(LoadFileError::PasswordRequired)?;
+            (zipcrypto::decrypt(&data, password, zipcrypto::check_byte(cdh))?, compression_method)
+        }
+        _ => (data, compression_method),
+    };
 
-    match compression_method {
-        COMPRESSION_METHOD_STORED => Result::Ok(data),
+    let buf = decompress(data, compression_method, cdh)?;
+
+    if verify_crc {
+        let actual = crc32fast::hash(&buf);
+        if actual != cdh.crc32 {
+            return Result::Err(LoadFileError::CrcMismatch {
+                expected: cdh.crc32,
+                actual,
+            });
+        }
+    }
+    Result::Ok(buf)
+}
+
+fn decompress(data: Vec<u8>, compression_method: u16, cdh: &CDHeader) -> Result<Vec<u8>, LoadFileError> {
+    let buf = match compression_method {
+        COMPRESSION_METHOD_STORED => data,
         COMPRESSION_METHOD_DEFLATED => {
-            let cursor = io::Cursor::new(data);
-            let mut decoder = libflate::deflate::Decoder::new(cursor);
-            let mut buf = Vec::with_capacity(uncompressed_size as usize);
+            let mut buf = Vec::with_capacity(capacity_hint(cdh.uncompressed_size, data.len()));
+            stream::inflate_to_end(&data, &mut buf)?;
+            buf
+        }
+        #[cfg(feature = "deflate64")]
+        COMPRESSION_METHOD_DEFLATE64 => {
+            let mut buf = Vec::with_capacity(capacity_hint(cdh.uncompressed_size, data.len()));
+            let mut decoder = deflate64::Deflate64Decoder::new(io::Cursor::new(data));
             decoder.read_to_end(&mut buf)?;
-            Result::Ok(buf)
+            buf
         }
-        _ => Result::Err(LoadFileError::UnsupportedCompressionMethod(
-            compression_method,
-        )),
+        #[cfg(feature = "bzip2")]
+        COMPRESSION_METHOD_BZIP2 => {
+            let mut buf = Vec::with_capacity(capacity_hint(cdh.uncompressed_size, data.len()));
+            let mut decoder = bzip2::read::BzDecoder::new(io::Cursor::new(data));
+            decoder.read_to_end(&mut buf)?;
+            buf
+        }
+        #[cfg(feature = "lzma")]
+        COMPRESSION_METHOD_LZMA => {
+            let mut buf = Vec::with_capacity(capacity_hint(cdh.uncompressed_size, data.len()));
+            // ZIP の LZMA はバージョン (2 バイト)、プロパティ長 (2 バイト)、プロパティ (5 バイト) の後に
+            // 圧縮データが続き、.lzma 形式と異なり展開後サイズを持たない
+            let mut reader = io::Cursor::new(data);
+            reader.seek(io::SeekFrom::Current(2))?;
+            let properties_length = reader.read_u16::<LittleEndian>()?;
+            if properties_length != 5 {
+                return Result::Err(invalid_data("Invalid LZMA properties length"));
+            }
+            // bit 1 が立っている場合は終端マーカーで終わる
+            let unpacked_size = if cdh.general_purpose_bit_flag & (1 << 1) != 0 {
+                None
+            } else {
+                Some(cdh.uncompressed_size)
+            };
+            let options = lzma_rs::decompress::Options {
+                unpacked_size: lzma_rs::decompress::UnpackedSize::UseProvided(unpacked_size),
+                ..Default::default()
+            };
+            lzma_rs::lzma_decompress_with_options(&mut reader, &mut buf, &options)
+                .map_err(invalid_data)?;
+            buf
+        }
+        #[cfg(feature = "zstd")]
+        COMPRESSION_METHOD_ZSTD => {
+            let mut buf = Vec::with_capacity(capacity_hint(cdh.uncompressed_size, data.len()));
+            let mut decoder = ruzstd::decoding::StreamingDecoder::new(io::Cursor::new(data))
+                .map_err(invalid_data)?;
+            decoder.read_to_end(&mut buf)?;
+            buf
+        }
+        #[cfg(feature = "xz")]
+        COMPRESSION_METHOD_XZ => {
+            let mut buf = Vec::with_capacity(capacity_hint(cdh.uncompressed_size, data.len()));
+            lzma_rs::xz_decompress(&mut io::Cursor::new(data), &mut buf).map_err(invalid_data)?;
+            buf
+        }
+        _ => {
+            return Result::Err(LoadFileError::UnsupportedCompressionMethod(
+                compression_method,
+            ))
+        }
+    };
+    Result::Ok(buf)
+}
+
+// 展開後のサイズは CD の値を信用せず、確保する量の目安にだけ使う。
+// 壊れた CD や ZIP64 拡張フィールドの大きな値で、巨大な領域を確保しないようにする
+fn capacity_hint(uncompressed_size: u64, compressed_length: usize) -> usize {
+    let estimate = (compressed_length as u64).saturating_mul(MAX_EXPECTED_COMPRESSION_RATIO);
+    cmp::min(cmp::min(uncompressed_size, estimate), MAX_CAPACITY_HINT) as usize
+}
+
+#[cfg(any(feature = "lzma", feature = "zstd", feature = "xz"))]
+fn invalid_data<E: std::fmt::Display>(error: E) -> LoadFileError {
+    LoadFileError::IOError(io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
+
+// CD の情報だけからエントリ全体を含む最小の範囲 (offset, length) を求める。
+// ローカルヘッダの拡張フィールド長は CD からは分からないため extra_field_allowance までを見込み、
+// 足りなかった場合は local_entry_length で不足分を求める
+pub fn entry_range(cdh: &CDHeader, extra_field_allowance: u64, cd_offset: u64) -> (u64, u64) {
+    let extra_field_length = cmp::max(cdh.extra_field_length as u64, extra_field_allowance);
+    // サイズやオフセットは CD の値なので、壊れていても桁あふれしないようにする
+    let length = (LFH_LENGTH + cdh.file_name_length as u64)
+        .saturating_add(extra_field_length)
+        .saturating_add(cdh.compressed_size)
+        .saturating_add(data_descriptor_length(cdh));
+    let offset = cdh.relative_offset_of_local_header;
+    let mut end = offset.saturating_add(length);
+    // エントリは CD より前にあるので CD をまたぐ分は取得しない
+    if offset < cd_offset {
+        end = cmp::min(end, cd_offset);
+    }
+    (offset, end - offset)
+}
+
+// chunk_offset から始まるアーカイブの断片 chunk の中にあるエントリを読み込む。
+// entry_offset はローカルヘッダのファイル上の位置。データディスクリプタは断片に含まれていなくてもよい
+pub fn load_file_from_chunk(
+    chunk: &[u8],
+    chunk_offset: u64,
+    entry_offset: u64,
+    cdh: &CDHeader,
+    verify_crc: bool,
+    password: Option<&[u8]>,
+) -> Result<Vec<u8>, LoadFileError> {
+    let start = entry_offset
+        .checked_sub(chunk_offset)
+        .filter(|&start| start.checked_add(LFH_LENGTH).is_some_and(|end| end <= chunk.len() as u64))
+        .ok_or(LoadFileError::ChunkNotCovered)? as usize;
+    let rest = &chunk[start..];
+    let length = local_entry_length(rest, cdh)?;
+    let descriptor_length = data_descriptor_length(cdh);
+    if (rest.len() as u64) < length - descriptor_length {
+        return Result::Err(LoadFileError::ChunkNotCovered);
+    }
+    let end = cmp::min(rest.len() as u64, length) as usize;
+    load_file(io::Cursor::new(rest[..end].to_vec()), cdh, verify_crc, password)
+}
+
+// ローカルヘッダ先頭の固定長部分から、エントリ全体に必要なバイト数を求める
+pub fn local_entry_length(header: &[u8], cdh: &CDHeader) -> Result<u64, LoadFileError> {
+    if (header.len() as u64) < LFH_LENGTH {
+        return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+    }
+    let mut cursor = io::Cursor::new(header);
+    let signature = cursor.read_u32::<LittleEndian>()?;
+    if signature != LFH_SIGNATURE {
+        return Result::Err(LoadFileError::UnmatchHeader);
+    }
+    cursor.set_position(26);
+    let file_name_length = cursor.read_u16::<LittleEndian>()? as u64;
+    let extra_field_length = cursor.read_u16::<LittleEndian>()? as u64;
+    // compressed_size は CD の値なので、壊れていても桁あふれしないようにする
+    Result::Ok(
+        (LFH_LENGTH + file_name_length + extra_field_length)
+            .saturating_add(cdh.compressed_size)
+            .saturating_add(data_descriptor_length(cdh)),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataDescriptor {
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+// データの直後にあるデータディスクリプタを読む。buf はディスクリプタの先頭から始まる。
+// シグネチャの有無とサイズの幅（32/64 ビット）は作成したツールによって異なるため、
+// 取りうる 4 通りの形式のうち CD の値と一致するものを選ぶ。zip64 の場合は 64 ビットの形式から試す。
+// どれとも一致しなければ最初に読めた形式の値を返す。どの形式でも長さが足りなければ None
+pub fn parse_data_descriptor(buf: &[u8], cdh: &CDHeader, zip64: bool) -> Option<DataDescriptor> {
+    let layouts = [(true, zip64), (false, zip64), (true, !zip64), (false, !zip64)];
+    let mut fallback = None;
+    for (signed, wide) in layouts {
+        let descriptor = match read_data_descriptor(buf, signed, wide) {
+            Some(descriptor) => descriptor,
+            None => continue,
+        };
+        if descriptor.crc32 == cdh.crc32
+            && descriptor.compressed_size == cdh.compressed_size
+            && descriptor.uncompressed_size == cdh.uncompressed_size
+        {
+            return Some(descriptor);
+        }
+        fallback.get_or_insert(descriptor);
+    }
+    fallback
+}
+
+fn read_data_descriptor(buf: &[u8], signed: bool, wide: bool) -> Option<DataDescriptor> {
+    let mut reader = io::Cursor::new(buf);
+    if signed && reader.read_u32::<LittleEndian>().ok()? != DATA_DESCRIPTOR_SIGNATURE {
+        return None;
+    }
+    let crc32 = reader.read_u32::<LittleEndian>().ok()?;
+    let (compressed_size, uncompressed_size) = if wide {
+        (reader.read_u64::<LittleEndian>().ok()?, reader.read_u64::<LittleEndian>().ok()?)
+    } else {
+        (
+            reader.read_u32::<LittleEndian>().ok()? as u64,
+            reader.read_u32::<LittleEndian>().ok()? as u64,
+        )
+    };
+    Some(DataDescriptor {
+        crc32,
+        compressed_size,
+        uncompressed_size,
+    })
+}
+
+// データディスクリプタの最大長（任意のシグネチャを含む）
+fn data_descriptor_length(cdh: &CDHeader) -> u64 {
+    if cdh.general_purpose_bit_flag & (1 << 3) == 0 {
+        0
+    } else if cdh.is_zip64 {
+        4 + 4 + 8 + 8
+    } else {
+        4 + 4 + 4 + 4
+    }
+}
+
+fn parse_aes_extra_field(field: &[u8]) -> io::Result<AesExtraField> {
+    let mut reader = io::Cursor::new(field);
+    let vendor_version = reader.read_u16::<LittleEndian>()?;
+    // Vendor ID ("AE")
+    reader.read_u16::<LittleEndian>()?;
+    let strength = reader.read_u8()?;
+    let compression_method = reader.read_u16::<LittleEndian>()?;
+    Result::Ok(AesExtraField {
+        vendor_version,
+        strength,
+        compression_method,
+    })
+}
+
+// 拡張フィールドから指定した Header ID のデータ部分を取り出す
+fn find_extra_field(extra_field: &[u8], header_id: u16) -> Option<&[u8]> {
+    let mut rest = extra_field;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        if rest.len() < 4 + size {
+            return None;
+        }
+        if id == header_id {
+            return Some(&rest[4..4 + size]);
+        }
+        rest = &rest[4 + size..];
+    }
+    None
+}
+
+// Info-ZIP Unicode Path / Comment 拡張フィールドは version (1), 元の値の CRC-32, UTF-8 の値からなる。
+// 元の値だけが他のツールで書き換えられた場合に備え、CRC-32 が一致しなければ使わない
+fn find_unicode_extra_field(extra_field: &[u8], header_id: u16, original: &[u8]) -> Option<String> {
+    let field = find_extra_field(extra_field, header_id)?;
+    if field.len() < 5 || field[0] != 1 {
+        return None;
+    }
+    let crc32 = u32::from_le_bytes([field[1], field[2], field[3], field[4]]);
+    if crc32 != crc32fast::hash(original) {
+        return None;
+    }
+    String::from_utf8(field[5..].to_vec()).ok()
+}
+
+fn decode_file_name(
+    buf: &[u8],
+    is_utf8: bool,
+    encoding: LegacyEncoding,
+) -> Result<String, FileNameError> {
+    if is_utf8 {
+        Result::Ok(String::from_utf8(buf.to_vec())?)
+    } else {
+        encoding
+            .decode(buf)
+            .ok_or(FileNameError::FromLegacyEncodingError)
     }
 }
 
-fn decode_file_name(buf: &Vec<u8>, is_utf8: bool) -> Result<String, FileNameError> {
+// コメントや変換できなかった名前のために、変換できない文字を U+FFFD に置き換えて返す
+pub fn decode_lossy(buf: &[u8], is_utf8: bool, encoding: LegacyEncoding) -> String {
     if is_utf8 {
-        Result::Ok(String::from_utf8(buf.clone())?)
+        String::from_utf8_lossy(buf).into_owned()
     } else {
-        let (res, _enc, errors) = SHIFT_JIS.decode(buf.as_slice());
-        if errors {
-            return Result::Err(FileNameError::FromSJISError);
+        encoding.decode_lossy(buf)
+    }
+}
+
+// シンボリックリンク link_name のリンク先 target を、アーカイブ内のパスとして解決する。
+// 絶対パスやアーカイブの外を指す場合は None
+pub fn resolve_symlink_target(link_name: &str, target: &str) -> Option<String> {
+    if target.is_empty() || target.starts_with('/') {
+        return None;
+    }
+    let mut parts: Vec<&str> = link_name.trim_end_matches('/').split('/').collect();
+    parts.pop();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+// 拡張タイムスタンプ (0x5455) は flags に続いて、flags で示された日時が Unix 時間（秒）で並ぶ。
+// CD 側のフィールドは更新日時しか持たないことが多いため、データが続く分だけ読む
+fn parse_extended_timestamp_extra_field(field: &[u8]) -> Timestamps {
+    let mut timestamps = Timestamps::default();
+    let (flags, mut rest) = match field.split_first() {
+        Some((&flags, rest)) => (flags, rest),
+        None => return timestamps,
+    };
+    let targets = [
+        &mut timestamps.modified,
+        &mut timestamps.accessed,
+        &mut timestamps.created,
+    ];
+    for (bit, target) in targets.into_iter().enumerate() {
+        if flags & (1 << bit) == 0 {
+            continue;
+        }
+        if rest.len() < 4 {
+            break;
+        }
+        let seconds = i32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        *target = Some(seconds as i64 * 1000);
+        rest = &rest[4..];
+    }
+    timestamps
+}
+
+// NTFS (0x000a) は 4 バイトの予約領域に続いて tag, size, data が並ぶ。
+// tag 0x0001 が更新・アクセス・作成日時の FILETIME を持つ
+fn parse_ntfs_extra_field(field: &[u8]) -> Option<Timestamps> {
+    let mut rest = field.get(4..)?;
+    while rest.len() >= 4 {
+        let tag = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let data = rest.get(4..4 + size)?;
+        if tag == 0x0001 && size >= 24 {
+            let filetime = |i: usize| {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(&data[i * 8..i * 8 + 8]);
+                filetime_to_unix_millis(u64::from_le_bytes(buf))
+            };
+            return Some(Timestamps {
+                modified: filetime(0),
+                accessed: filetime(1),
+                created: filetime(2),
+            });
         }
-        Result::Ok(res.into_owned())
+        rest = &rest[4 + size..];
+    }
+    None
+}
+
+// FILETIME は 1601-01-01 からの 100 ナノ秒単位。0 は日時なしを表す
+fn filetime_to_unix_millis(filetime: u64) -> Option<i64> {
+    if filetime == 0 {
+        return None;
+    }
+    Some((filetime / 10_000) as i64 - 11_644_473_600_000)
+}
+
+// MS-DOS 形式の日付・時刻を Unix エポックからのミリ秒に変換する。
+// タイムゾーンの情報は持たないため UTC として扱う。日付が 0 のものや範囲外の値は日時なしとみなす
+pub fn dos_datetime_to_unix_millis(date: u16, time: u16) -> Option<i64> {
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0x0F) as i64;
+    let day = (date & 0x1F) as i64;
+    let hour = (time >> 11) as i64;
+    let minute = ((time >> 5) & 0x3F) as i64;
+    let second = ((time & 0x1F) * 2) as i64;
+
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    // days_from_civil (http://howardhinnant.github.io/date_algorithms.html)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(((days * 24 + hour) * 60 + minute) * 60_000 + second * 1000)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
     }
 }
 
@@ -298,30 +1094,18 @@ impl From<FromUtf8Error> for FileNameError {
     }
 }
 
-impl From<FileNameError> for ParseCDError {
-    fn from(_: FileNameError) -> Self {
-        ParseCDError::FileNameConversionError
-    }
-}
-
 impl From<io::Error> for LoadFileError {
     fn from(error: io::Error) -> Self {
         LoadFileError::IOError(error)
     }
 }
 
-impl From<FileNameError> for LoadFileError {
-    fn from(_: FileNameError) -> Self {
-        LoadFileError::FileNameConversionError
-    }
-}
-
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     // Helper: 最小構成のEOCD（22バイト）を生成
-    fn create_minimal_eocd(entry_count: u16, cd_size: u32, cd_offset: u32) -> Vec<u8> {
+    pub(crate) fn create_minimal_eocd(entry_count: u16, cd_size: u32, cd_offset: u32) -> Vec<u8> {
         let mut data = Vec::new();
         // Signature
         data.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
@@ -343,7 +1127,7 @@ mod tests {
     }
 
     // Helper: Central Directoryヘッダーを生成
-    fn create_cd_header(
+    pub(crate) fn create_cd_header(
         file_name: &str,
         local_header_offset: u32,
         compressed_size: u32,
@@ -396,7 +1180,7 @@ mod tests {
     }
 
     // Helper: Local File Headerを生成
-    fn create_local_file_header(
+    pub(crate) fn create_local_file_header(
         file_name: &str,
         file_data: &[u8],
         compression_method: u16,
@@ -443,7 +1227,7 @@ mod tests {
         let eocd_data = create_minimal_eocd(1, 46, 100);
         let mut cursor = io::Cursor::new(eocd_data);
 
-        let result = parse_eocd(&mut cursor);
+        let result = parse_eocd(&mut cursor, None);
         assert!(result.is_ok());
 
         let eocd = result.unwrap();
@@ -454,6 +1238,101 @@ mod tests {
         assert_eq!(eocd.eocd_size, 22);
     }
 
+    #[test]
+    fn test_eocd_base_offset() {
+        // 100 バイトの CD の後に EOCD が続くアーカイブ
+        let mut data = vec![0u8; 100];
+        data.extend(create_minimal_eocd(1, 100, 0));
+        let eocd = parse_eocd(&mut io::Cursor::new(data.clone()), None).unwrap();
+        assert_eq!(eocd.base_offset(0), 0);
+
+        // 先頭に 512 バイトのスタブが付いている
+        let mut prepended = vec![0u8; 512];
+        prepended.extend(&data);
+        let eocd = parse_eocd(&mut io::Cursor::new(prepended), None).unwrap();
+        assert_eq!(eocd.base_offset(0), 512);
+
+        // ファイル末尾だけを渡した場合は、その位置を data_offset で指定する
+        let tail = data[50..].to_vec();
+        let eocd = parse_eocd(&mut io::Cursor::new(tail), Some(512 + 50)).unwrap();
+        assert_eq!(eocd.base_offset(512 + 50), 512);
+        // 位置が分からない場合は補正しない
+        assert_eq!(eocd.base_offset(0), 0);
+    }
+
+    #[test]
+    fn test_eocd_decoded_comment() {
+        // "ライセンス: CC-BY" (Shift_JIS)
+        let comment = [0x83, 0x89, 0x83, 0x43, 0x83, 0x5A, 0x83, 0x93, 0x83, 0x58, b':', b' ', b'C', b'C', b'-', b'B', b'Y'];
+        let mut data = create_minimal_eocd(0, 0, 0);
+        data[20..22].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+        data.extend_from_slice(&comment);
+
+        let eocd = parse_eocd(&mut io::Cursor::new(data), None).unwrap();
+        assert_eq!(eocd.comment, comment);
+        let encoding = LegacyEncoding::for_label("shift_jis").unwrap();
+        assert_eq!(eocd.decoded_comment(encoding), "ライセンス: CC-BY");
+    }
+
+    #[test]
+    fn test_parse_eocd_signature_not_found() {
+        let result = parse_eocd(&mut io::Cursor::new(vec![0u8; 100]), None);
+        assert!(matches!(result, Err(ParseEOCDError::InvalidSignature)));
+
+        // 探索範囲より前にある EOCD は見つけない
+        let mut data = create_minimal_eocd(0, 0, 0);
+        data.resize(EOCD_LENGTH as usize + MAX_EOCD_SEARCH_LENGTH as usize, 0);
+        let result = parse_eocd(&mut io::Cursor::new(data), None);
+        assert!(matches!(result, Err(ParseEOCDError::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_parse_eocd_signature_in_comment() {
+        // コメントの末尾に EOCD と同じバイト列が含まれている
+        let fake = create_minimal_eocd(9, 1, 2);
+        let mut data = vec![0u8; 46];
+        data.extend(create_minimal_eocd(1, 46, 0));
+        data[46 + 20..46 + 22].copy_from_slice(&(fake.len() as u16).to_le_bytes());
+        data.extend(&fake);
+
+        let eocd = parse_eocd(&mut io::Cursor::new(data), None).unwrap();
+        assert_eq!(eocd.eocd_offset, 46);
+        assert_eq!(eocd.total_number_of_entries_in_cd, 1);
+        assert_eq!(eocd.comment, fake);
+    }
+
+    #[test]
+    fn test_parse_eocd_forged_record_in_comment() {
+        // コメントに、EOCD より後ろに CD があることになる偽の EOCD が含まれている
+        let fake = create_minimal_eocd(1, 46, 1000);
+        let mut data = vec![0u8; 46];
+        data.extend(create_minimal_eocd(1, 46, 0));
+        data[46 + 20..46 + 22].copy_from_slice(&(fake.len() as u16).to_le_bytes());
+        data.extend(&fake);
+
+        // 位置が分からなければ CD の範囲は検証できない
+        let eocd = parse_eocd(&mut io::Cursor::new(data.clone()), None).unwrap();
+        assert_eq!(eocd.eocd_offset, 46 + 22);
+
+        let eocd = parse_eocd(&mut io::Cursor::new(data), Some(0)).unwrap();
+        assert_eq!(eocd.eocd_offset, 46);
+        assert_eq!(eocd.cd_offset, 0);
+    }
+
+    #[test]
+    fn test_parse_eocd_invalid_records() {
+        // EOCD の後ろに余分なデータがある
+        let mut data = create_minimal_eocd(0, 0, 0);
+        data.extend_from_slice(b"garbage");
+        let result = parse_eocd(&mut io::Cursor::new(data), None);
+        assert!(matches!(result, Err(ParseEOCDError::CommentLengthMismatch)));
+
+        // 5 エントリなのに CD が 10 バイトしかない
+        let data = create_minimal_eocd(5, 10, 0);
+        let result = parse_eocd(&mut io::Cursor::new(data), None);
+        assert!(matches!(result, Err(ParseEOCDError::ImplausibleRecord)));
+    }
+
     #[test]
     fn test_parse_eocd_with_comment() {
         let mut data = create_minimal_eocd(0, 0, 0);
@@ -464,7 +1343,7 @@ mod tests {
         data.extend_from_slice(b"hello");
 
         let mut cursor = io::Cursor::new(data);
-        let result = parse_eocd(&mut cursor);
+        let result = parse_eocd(&mut cursor, None);
         assert!(result.is_ok());
 
         let eocd = result.unwrap();
@@ -477,7 +1356,7 @@ mod tests {
         let data = vec![0u8; 21]; // 22バイト未満
         let mut cursor = io::Cursor::new(data);
 
-        let result = parse_eocd(&mut cursor);
+        let result = parse_eocd(&mut cursor, None);
         assert!(matches!(result, Err(ParseEOCDError::TooShortDataLength)));
     }
 
@@ -488,10 +1367,10 @@ mod tests {
         let cd_data = create_cd_header("test.txt", 0, 5, 5, 0x12345678, COMPRESSION_METHOD_STORED, true);
         let mut cursor = io::Cursor::new(cd_data);
 
-        let result = parse_cd(&mut cursor, 1);
+        let result = parse_cd(&mut cursor, 1, Some(LegacyEncoding::default()));
         assert!(result.is_ok());
 
-        let headers = result.unwrap();
+        let (headers, _) = result.unwrap();
         assert_eq!(headers.len(), 1);
         assert_eq!(headers[0].file_name, "test.txt");
         assert_eq!(headers[0].compressed_size, 5);
@@ -507,7 +1386,7 @@ mod tests {
         data[0..4].copy_from_slice(&0x12345678u32.to_le_bytes());
 
         let mut cursor = io::Cursor::new(data);
-        let result = parse_cd(&mut cursor, 1);
+        let result = parse_cd(&mut cursor, 1, Some(LegacyEncoding::default()));
         assert!(matches!(result, Err(ParseCDError::InvalidSignature)));
     }
 
@@ -538,14 +1417,18 @@ mod tests {
             external_file_attributes: 0,
             relative_offset_of_local_header: 0,
             file_name: "test.txt".to_string(),
+            file_name_bytes: "test.txt".as_bytes().to_vec(),
+            is_name_lossy: false,
             extra_field: vec![],
             file_comment: vec![],
             is_utf8: true,
             is_encrypted: false,
+            is_zip64: false,
+            aes: None,
         };
 
         let cursor = io::Cursor::new(lfh);
-        let result = load_file(cursor, &cdh);
+        let result = load_file(cursor, &cdh, true, None);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), b"Hello");
     }
@@ -575,14 +1458,18 @@ mod tests {
             external_file_attributes: 0,
             relative_offset_of_local_header: 0,
             file_name: "".to_string(),
+            file_name_bytes: "".as_bytes().to_vec(),
+            is_name_lossy: false,
             extra_field: vec![],
             file_comment: vec![],
             is_utf8: false,
             is_encrypted: false,
+            is_zip64: false,
+            aes: None,
         };
 
         let cursor = io::Cursor::new(data);
-        let result = load_file(cursor, &cdh);
+        let result = load_file(cursor, &cdh, true, None);
         assert!(matches!(result, Err(LoadFileError::UnmatchHeader)));
     }
 
@@ -591,7 +1478,7 @@ mod tests {
     #[test]
     fn test_decode_file_name_utf8() {
         let name = "test.txt".as_bytes().to_vec();
-        let result = decode_file_name(&name, true);
+        let result = decode_file_name(&name, true, LegacyEncoding::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "test.txt");
     }
@@ -599,7 +1486,7 @@ mod tests {
     #[test]
     fn test_decode_file_name_utf8_japanese() {
         let name = "テスト.txt".as_bytes().to_vec();
-        let result = decode_file_name(&name, true);
+        let result = decode_file_name(&name, true, LegacyEncoding::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "テスト.txt");
     }
@@ -608,18 +1495,89 @@ mod tests {
     fn test_decode_file_name_ascii_as_sjis() {
         // ASCIIはShift_JISでも同じ
         let name = "test.txt".as_bytes().to_vec();
-        let result = decode_file_name(&name, false);
+        let result = decode_file_name(&name, false, LegacyEncoding::for_label("shift_jis").unwrap());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "test.txt");
     }
 
+    #[test]
+    fn test_decode_file_name_legacy_encodings() {
+        // "テスト" (Shift_JIS)
+        let sjis = [0x83, 0x65, 0x83, 0x58, 0x83, 0x67];
+        let result = decode_file_name(&sjis, false, LegacyEncoding::for_label("shift_jis").unwrap());
+        assert_eq!(result.unwrap(), "テスト");
+
+        // "测试" (GBK)
+        let gbk = [0xB2, 0xE2, 0xCA, 0xD4];
+        let result = decode_file_name(&gbk, false, LegacyEncoding::for_label("gbk").unwrap());
+        assert_eq!(result.unwrap(), "测试");
+
+        // 既定の CP437 では 1 バイト 1 文字として解釈される
+        let result = decode_file_name(&gbk, false, LegacyEncoding::default());
+        assert_eq!(result.unwrap(), "▓Γ╩╘");
+    }
+
+    #[test]
+    fn test_decode_file_name_invalid_sequence() {
+        // Shift_JIS の 2 バイト文字の途中で終わっている
+        let name = [0x82];
+        let result = decode_file_name(&name, false, LegacyEncoding::for_label("shift_jis").unwrap());
+        assert!(matches!(result, Err(FileNameError::FromLegacyEncodingError)));
+    }
+
+    #[test]
+    fn test_parse_cd_with_legacy_encoding() {
+        // "Файл.txt" (windows-1251)
+        let name = [0xD4, 0xE0, 0xE9, 0xEB, b'.', b't', b'x', b't'];
+        let mut cd_data = create_cd_header("________", 0, 5, 5, 0, COMPRESSION_METHOD_STORED, false);
+        cd_data[46..].copy_from_slice(&name);
+
+        let mut cursor = io::Cursor::new(cd_data);
+        let headers = parse_cd(&mut cursor, 1, Some(LegacyEncoding::for_label("windows-1251").unwrap())).unwrap().0;
+        assert_eq!(headers[0].file_name, "Файл.txt");
+        assert_eq!(headers[0].file_name_bytes, name);
+        assert!(!headers[0].is_utf8);
+    }
+
+    #[test]
+    fn test_parse_cd_detects_encoding() {
+        // "テスト.txt" (Shift_JIS)
+        let name = [0x83, 0x65, 0x83, 0x58, 0x83, 0x67, b'.', b't', b'x', b't'];
+        let mut cd_data = create_cd_header("utf8.txt", 0, 5, 5, 0, COMPRESSION_METHOD_STORED, true);
+        let mut legacy = create_cd_header("__________", 0, 5, 5, 0, COMPRESSION_METHOD_STORED, false);
+        legacy[46..].copy_from_slice(&name);
+        cd_data.extend_from_slice(&legacy);
+
+        let (headers, encoding) = parse_cd(&mut io::Cursor::new(cd_data), 2, None).unwrap();
+        assert_eq!(encoding.name(), "Shift_JIS");
+        assert_eq!(headers[0].file_name, "utf8.txt");
+        assert_eq!(headers[1].file_name, "テスト.txt");
+    }
+
+    #[test]
+    fn test_parse_cd_undecodable_name() {
+        // Shift_JIS の 2 バイト文字の途中で切れた名前
+        let name = [b'a', 0x82];
+        let mut cd_data = create_cd_header("__", 0, 5, 5, 0, COMPRESSION_METHOD_STORED, false);
+        cd_data[46..].copy_from_slice(&name);
+        cd_data.extend(create_cd_header("ok.txt", 100, 5, 5, 0, COMPRESSION_METHOD_STORED, true));
+
+        let encoding = LegacyEncoding::for_label("shift_jis").unwrap();
+        let (headers, _) = parse_cd(&mut io::Cursor::new(cd_data), 2, Some(encoding)).unwrap();
+        assert_eq!(headers[0].file_name, "a\u{FFFD}");
+        assert_eq!(headers[0].file_name_bytes, name);
+        assert!(headers[0].is_name_lossy);
+        assert_eq!(headers[1].file_name, "ok.txt");
+        assert!(!headers[1].is_name_lossy);
+    }
+
     // ===== 追加テスト =====
 
     // Helper: テスト用CDHeader構造体を生成
-    fn create_cdh_for_test(
+    pub(crate) fn create_cdh_for_test(
         file_name: &str,
-        compressed_size: u32,
-        uncompressed_size: u32,
+        compressed_size: u64,
+        uncompressed_size: u64,
         crc32: u32,
         compression_method: u16,
     ) -> CDHeader {
@@ -642,15 +1600,19 @@ mod tests {
             external_file_attributes: 0,
             relative_offset_of_local_header: 0,
             file_name: file_name.to_string(),
+            file_name_bytes: file_name.as_bytes().to_vec(),
+            is_name_lossy: false,
             extra_field: vec![],
             file_comment: vec![],
             is_utf8: true,
             is_encrypted: false,
+            is_zip64: false,
+            aes: None,
         }
     }
 
     // Helper: DEFLATE圧縮用Local File Headerを生成
-    fn create_local_file_header_deflated(
+    pub(crate) fn create_local_file_header_deflated(
         file_name: &str,
         compressed_data: &[u8],
         uncompressed_size: u32,
@@ -691,16 +1653,13 @@ mod tests {
 
     #[test]
     fn test_load_file_deflated() {
-        use libflate::deflate::Encoder;
-        use std::io::Write;
+        use miniz_oxide::deflate::compress_to_vec;
 
         let original_data = b"Hello, World! This is a test for DEFLATE compression.";
-        let crc32 = 0x7D6C5C3Au32; // 事前計算したCRC32
+        let crc32 = 0x28DC4FDDu32; // 事前計算したCRC32
 
         // DEFLATEで圧縮
-        let mut encoder = Encoder::new(Vec::new());
-        encoder.write_all(original_data).unwrap();
-        let compressed_data = encoder.finish().into_result().unwrap();
+        let compressed_data = compress_to_vec(original_data, 6);
 
         let lfh = create_local_file_header_deflated(
             "test.txt",
@@ -711,21 +1670,39 @@ mod tests {
 
         let cdh = create_cdh_for_test(
             "test.txt",
-            compressed_data.len() as u32,
-            original_data.len() as u32,
+            compressed_data.len() as u64,
+            original_data.len() as u64,
             crc32,
             COMPRESSION_METHOD_DEFLATED,
         );
 
         let cursor = io::Cursor::new(lfh);
-        let result = load_file(cursor, &cdh);
+        let result = load_file(cursor, &cdh, true, None);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), original_data);
     }
 
     #[test]
-    fn test_parse_cd_multiple_entries() {
-        let mut cd_data = Vec::new();
+    fn test_decompress_with_bogus_uncompressed_size() {
+        use miniz_oxide::deflate::compress_to_vec;
+
+        let original_data = b"Hello, World! This is a test for DEFLATE compression.";
+        let crc32 = crc32fast::hash(original_data);
+        let compressed_data = compress_to_vec(original_data, 6);
+        // 壊れた ZIP64 拡張フィールドなどで展開後のサイズが巨大になっていても、その分を確保しない
+        let cdh = create_cdh_for_test("test.txt", compressed_data.len() as u64, u64::MAX, crc32, COMPRESSION_METHOD_DEFLATED);
+
+        let result = decompress(compressed_data, COMPRESSION_METHOD_DEFLATED, &cdh).unwrap();
+        assert_eq!(result, original_data);
+
+        assert_eq!(capacity_hint(u64::MAX, 100), 3200);
+        assert_eq!(capacity_hint(50, 100), 50);
+        assert_eq!(capacity_hint(u64::MAX, usize::MAX), MAX_CAPACITY_HINT as usize);
+    }
+
+    #[test]
+    fn test_parse_cd_multiple_entries() {
+        let mut cd_data = Vec::new();
 
         // 1つ目のエントリ
         cd_data.extend(create_cd_header(
@@ -741,10 +1718,10 @@ mod tests {
         ));
 
         let mut cursor = io::Cursor::new(cd_data);
-        let result = parse_cd(&mut cursor, 3);
+        let result = parse_cd(&mut cursor, 3, Some(LegacyEncoding::default()));
         assert!(result.is_ok());
 
-        let headers = result.unwrap();
+        let (headers, _) = result.unwrap();
         assert_eq!(headers.len(), 3);
 
         assert_eq!(headers[0].file_name, "file1.txt");
@@ -788,8 +1765,8 @@ mod tests {
             last_mod_file_time: 0,
             last_mod_file_date: 0,
             crc32: 0,
-            compressed_size: file_data.len() as u32,
-            uncompressed_size: file_data.len() as u32,
+            compressed_size: file_data.len() as u64,
+            uncompressed_size: file_data.len() as u64,
             file_name_length: file_name.len() as u16,
             extra_field_length: 0,
             file_comment_length: 0,
@@ -798,14 +1775,18 @@ mod tests {
             external_file_attributes: 0,
             relative_offset_of_local_header: 0,
             file_name: file_name.to_string(),
+            file_name_bytes: file_name.as_bytes().to_vec(),
+            is_name_lossy: false,
             extra_field: vec![],
             file_comment: vec![],
             is_utf8: true,
             is_encrypted: false,
+            is_zip64: false,
+            aes: None,
         };
 
         let cursor = io::Cursor::new(lfh);
-        let result = load_file(cursor, &cdh);
+        let result = load_file(cursor, &cdh, true, None);
         assert!(matches!(
             result,
             Err(LoadFileError::UnsupportedCompressionMethod(99))
@@ -821,8 +1802,934 @@ mod tests {
         let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
 
         let cursor = io::Cursor::new(lfh);
-        let result = load_file(cursor, &cdh);
+        let result = load_file(cursor, &cdh, true, None);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), b"Hello");
     }
+
+    // ===== ZIP64 tests =====
+
+    // Helper: ZIP64 EOCD レコード + ロケータ + EOCD を生成
+    fn create_zip64_eocd(entry_count: u64, cd_size: u64, cd_offset: u64, record_offset: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        // ZIP64 EOCD record
+        data.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+        data.extend_from_slice(&(ZIP64_EOCD_LENGTH - 12).to_le_bytes()); // size of record
+        data.extend_from_slice(&45u16.to_le_bytes()); // version made by
+        data.extend_from_slice(&45u16.to_le_bytes()); // version needed
+        data.extend_from_slice(&0u32.to_le_bytes()); // number of this disk
+        data.extend_from_slice(&0u32.to_le_bytes()); // disk with CD
+        data.extend_from_slice(&entry_count.to_le_bytes());
+        data.extend_from_slice(&entry_count.to_le_bytes());
+        data.extend_from_slice(&cd_size.to_le_bytes());
+        data.extend_from_slice(&cd_offset.to_le_bytes());
+        // ZIP64 EOCD locator
+        data.extend_from_slice(&ZIP64_EOCD_LOCATOR_SIGNATURE.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes()); // disk with ZIP64 EOCD
+        data.extend_from_slice(&record_offset.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes()); // total number of disks
+        // EOCD（値はすべて ZIP64 側を参照させる）
+        data.extend(create_minimal_eocd(0xFFFF, 0xFFFFFFFF, 0xFFFFFFFF));
+        data
+    }
+
+    // Helper: ZIP64 拡張情報フィールドを生成
+    fn create_zip64_extra_field(values: &[u64]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&EXTRA_FIELD_ZIP64.to_le_bytes());
+        data.extend_from_slice(&((values.len() * 8) as u16).to_le_bytes());
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_eocd_zip64() {
+        // 先頭にダミーデータを置き、バッファ内オフセットが 0 でない状態にする
+        let mut data = vec![0u8; 10];
+        data.extend(create_zip64_eocd(70000, 0x1_2345_6789, 0x2_0000_0000, 0x3_0000_0000));
+
+        let mut cursor = io::Cursor::new(data);
+        let eocd = parse_eocd(&mut cursor, None).unwrap();
+        assert!(eocd.is_zip64);
+        assert_eq!(eocd.total_number_of_entries_in_cd, 70000);
+        assert_eq!(eocd.cd_size, 0x1_2345_6789);
+        assert_eq!(eocd.cd_offset, 0x2_0000_0000);
+        assert_eq!(eocd.eocd_offset, 10);
+        assert_eq!(eocd.eocd_size, ZIP64_EOCD_LENGTH + ZIP64_EOCD_LOCATOR_LENGTH + EOCD_LENGTH);
+    }
+
+    #[test]
+    fn test_parse_eocd_zip64_uses_locator_offset() {
+        let data = create_zip64_eocd(70000, 0x1_2345_6789, 0x2_0000_0000, 0);
+        let (record, rest) = data.split_at(ZIP64_EOCD_LENGTH as usize);
+        // 拡張データ領域を 100 バイト付け、その中にロケータの直前で終わる偽のレコードを置く
+        let mut record = record.to_vec();
+        record[4..12].copy_from_slice(&(ZIP64_EOCD_LENGTH - 12 + 100).to_le_bytes());
+        let mut sector = vec![0u8; 100];
+        sector[20..24].copy_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+        sector[24..32].copy_from_slice(&(100 - 20 - 12u64).to_le_bytes());
+        let mut data = record;
+        data.extend(sector);
+        data.extend_from_slice(rest);
+
+        let eocd = parse_eocd(&mut io::Cursor::new(data), Some(0)).unwrap();
+        assert_eq!(eocd.eocd_offset, 0);
+        assert_eq!(eocd.total_number_of_entries_in_cd, 70000);
+        assert_eq!(eocd.cd_size, 0x1_2345_6789);
+    }
+
+    #[test]
+    fn test_parse_eocd_zip64_without_locator() {
+        let data = create_minimal_eocd(0xFFFF, 0xFFFFFFFF, 0xFFFFFFFF);
+        let mut cursor = io::Cursor::new(data);
+
+        let result = parse_eocd(&mut cursor, None);
+        assert!(matches!(result, Err(ParseEOCDError::Zip64RecordNotFound)));
+    }
+
+    #[test]
+    fn test_parse_cd_zip64_extra_field() {
+        let mut cd_data = create_cd_header(
+            "large.bin", 0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF, 0x12345678, COMPRESSION_METHOD_STORED, true,
+        );
+        let extra = create_zip64_extra_field(&[0x1_0000_0000, 0x1_0000_0000, 0x2_0000_0000]);
+        // Extra field length（オフセット30-31）を書き換えて拡張フィールドを追加
+        cd_data[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        cd_data.extend(extra);
+
+        let mut cursor = io::Cursor::new(cd_data);
+        let headers = parse_cd(&mut cursor, 1, Some(LegacyEncoding::default())).unwrap().0;
+        assert!(headers[0].is_zip64);
+        assert_eq!(headers[0].uncompressed_size, 0x1_0000_0000);
+        assert_eq!(headers[0].compressed_size, 0x1_0000_0000);
+        assert_eq!(headers[0].relative_offset_of_local_header, 0x2_0000_0000);
+    }
+
+    #[test]
+    fn test_load_file_zip64_local_header() {
+        let file_content = b"Hello";
+        let crc32 = 0xF7D18982u32;
+        let mut lfh = create_local_file_header("test.txt", file_content, COMPRESSION_METHOD_STORED, crc32, true);
+        let extra = create_zip64_extra_field(&[5, 5]);
+        // サイズを 0xFFFFFFFF にして ZIP64 拡張情報から読ませる
+        lfh[18..22].copy_from_slice(&0xFFFFFFFFu32.to_le_bytes());
+        lfh[22..26].copy_from_slice(&0xFFFFFFFFu32.to_le_bytes());
+        lfh[28..30].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        let data_start = 30 + "test.txt".len();
+        lfh.splice(data_start..data_start, extra);
+
+        let mut cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        cdh.is_zip64 = true;
+
+        let cursor = io::Cursor::new(lfh);
+        let result = load_file(cursor, &cdh, true, None);
+        assert_eq!(result.unwrap(), b"Hello");
+    }
+
+    // ===== entry range tests =====
+
+    #[test]
+    fn test_entry_range_from_cd_sizes() {
+        let mut cdh = create_cdh_for_test("test.txt", 100, 200, 0, COMPRESSION_METHOD_DEFLATED);
+        cdh.relative_offset_of_local_header = 1000;
+
+        let (offset, length) = entry_range(&cdh, 16, 10000);
+        assert_eq!(offset, 1000);
+        assert_eq!(length, 30 + 8 + 16 + 100);
+    }
+
+    #[test]
+    fn test_entry_range_with_data_descriptor_clamped_to_cd() {
+        let mut cdh = create_cdh_for_test("test.txt", 100, 200, 0, COMPRESSION_METHOD_DEFLATED);
+        cdh.general_purpose_bit_flag |= 1 << 3;
+        cdh.relative_offset_of_local_header = 1000;
+
+        let (_, length) = entry_range(&cdh, 16, 10000);
+        assert_eq!(length, 30 + 8 + 16 + 100 + 16);
+
+        // CD の直前のエントリは CD の先頭までに切り詰める
+        let (_, length) = entry_range(&cdh, 16, 1150);
+        assert_eq!(length, 150);
+    }
+
+    #[test]
+    fn test_entry_range_with_bogus_sizes() {
+        let mut cdh = create_cdh_for_test("test.txt", u64::MAX, 200, 0, COMPRESSION_METHOD_DEFLATED);
+        cdh.general_purpose_bit_flag |= 1 << 3;
+        cdh.relative_offset_of_local_header = 1000;
+
+        // 壊れた ZIP64 のサイズでも桁あふれせず、CD の先頭までに収まる
+        let (offset, length) = entry_range(&cdh, 16, 10000);
+        assert_eq!(offset, 1000);
+        assert_eq!(length, 9000);
+
+        // オフセットも壊れている場合は u64 の上限で打ち切る
+        cdh.relative_offset_of_local_header = u64::MAX - 10;
+        let (offset, length) = entry_range(&cdh, 16, 10000);
+        assert_eq!(offset, u64::MAX - 10);
+        assert_eq!(length, 10);
+    }
+
+    #[test]
+    fn test_local_entry_length_longer_extra_field() {
+        let mut lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, 0, true);
+        // Extra field length（オフセット28-29）を 100 に書き換える
+        lfh[28..30].copy_from_slice(&100u16.to_le_bytes());
+        let cdh = create_cdh_for_test("test.txt", 5, 5, 0, COMPRESSION_METHOD_STORED);
+
+        let length = local_entry_length(&lfh[..30], &cdh).unwrap();
+        assert_eq!(length, 30 + 8 + 100 + 5);
+    }
+
+    #[test]
+    fn test_load_file_data_descriptor_with_trailing_bytes() {
+        let file_content = b"Hello";
+        let crc32 = 0xF7D18982u32;
+        let mut lfh = create_local_file_header("test.txt", file_content, COMPRESSION_METHOD_STORED, 0, true);
+        lfh[6..8].copy_from_slice(&((1u16 << 11) | (1 << 3)).to_le_bytes());
+        // 署名付きデータディスクリプタ
+        lfh.extend_from_slice(&DATA_DESCRIPTOR_SIGNATURE.to_le_bytes());
+        lfh.extend_from_slice(&crc32.to_le_bytes());
+        lfh.extend_from_slice(&5u32.to_le_bytes());
+        lfh.extend_from_slice(&5u32.to_le_bytes());
+        // 範囲に含まれた次のエントリの先頭
+        lfh.extend_from_slice(&LFH_SIGNATURE.to_le_bytes());
+
+        let mut cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        cdh.general_purpose_bit_flag |= 1 << 3;
+
+        let cursor = io::Cursor::new(lfh);
+        let result = load_file(cursor, &cdh, true, None);
+        assert_eq!(result.unwrap(), b"Hello");
+    }
+
+    // ===== CRC-32 verification tests =====
+
+    #[test]
+    fn test_load_file_crc_mismatch() {
+        let file_content = b"Hellp"; // 破損したデータ
+        let crc32 = 0xF7D18982u32; // CRC32 of "Hello"
+        let lfh = create_local_file_header("test.txt", file_content, COMPRESSION_METHOD_STORED, crc32, true);
+        let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+
+        let result = load_file(io::Cursor::new(lfh.clone()), &cdh, true, None);
+        assert!(matches!(
+            result,
+            Err(LoadFileError::CrcMismatch { expected: 0xF7D18982, .. })
+        ));
+
+        // 検証を省略した場合はそのまま返す
+        let result = load_file(io::Cursor::new(lfh), &cdh, false, None);
+        assert_eq!(result.unwrap(), b"Hellp");
+    }
+
+    #[test]
+    fn test_load_file_truncated_range() {
+        let crc32 = 0xF7D18982u32; // CRC32 of "Hello"
+        let mut lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, crc32, true);
+        // ダウンロードが途中で切れて 1 バイト足りない
+        lfh.pop();
+        let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, None);
+        assert!(matches!(
+            result,
+            Err(LoadFileError::IOError(ref error)) if error.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    // ===== entry metadata tests =====
+
+    #[test]
+    fn test_dos_datetime_to_unix_millis() {
+        // 2021-03-04 05:06:08
+        let date = ((2021 - 1980) << 9) | (3 << 5) | 4;
+        let time = (5 << 11) | (6 << 5) | (8 / 2);
+        assert_eq!(dos_datetime_to_unix_millis(date, time), Some(1614834368000));
+        // 1980-01-01 00:00:00
+        assert_eq!(dos_datetime_to_unix_millis((1 << 5) | 1, 0), Some(315532800000));
+        // 2024-02-29 23:59:58
+        let date = ((2024 - 1980) << 9) | (2 << 5) | 29;
+        let time = (23 << 11) | (59 << 5) | (58 / 2);
+        assert_eq!(dos_datetime_to_unix_millis(date, time), Some(1709251198000));
+    }
+
+    #[test]
+    fn test_dos_datetime_invalid() {
+        // 日付なし
+        assert_eq!(dos_datetime_to_unix_millis(0, 0), None);
+        // 2023-02-29
+        assert_eq!(dos_datetime_to_unix_millis(((2023 - 1980) << 9) | (2 << 5) | 29, 0), None);
+        // 13 月
+        assert_eq!(dos_datetime_to_unix_millis((13 << 5) | 1, 0), None);
+        // 24 時
+        assert_eq!(dos_datetime_to_unix_millis((1 << 5) | 1, 24 << 11), None);
+        // 60 秒
+        assert_eq!(dos_datetime_to_unix_millis((1 << 5) | 1, 30), None);
+    }
+
+    #[test]
+    fn test_extended_timestamp_extra_field() {
+        let mut cdh = create_cdh_for_test("a.txt", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        cdh.last_mod_file_date = (1 << 5) | 1;
+        // flags は 3 つとも立っているが、CD には更新日時しか入っていない
+        cdh.extra_field = vec![0x55, 0x54, 5, 0, 0x07];
+        cdh.extra_field.extend_from_slice(&1_600_000_000i32.to_le_bytes());
+
+        let timestamps = cdh.timestamps();
+        assert_eq!(timestamps.modified, Some(1_600_000_000_000));
+        assert_eq!(timestamps.accessed, None);
+        assert_eq!(timestamps.created, None);
+
+        // ローカルヘッダと同じく 3 つとも入っている場合
+        cdh.extra_field = vec![0x55, 0x54, 13, 0, 0x07];
+        for seconds in [1_600_000_000i32, 1_600_000_100, -1] {
+            cdh.extra_field.extend_from_slice(&seconds.to_le_bytes());
+        }
+        let timestamps = cdh.timestamps();
+        assert_eq!(timestamps.modified, Some(1_600_000_000_000));
+        assert_eq!(timestamps.accessed, Some(1_600_000_100_000));
+        assert_eq!(timestamps.created, Some(-1000));
+    }
+
+    #[test]
+    fn test_ntfs_extra_field() {
+        let mut cdh = create_cdh_for_test("a.txt", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        cdh.last_mod_file_date = (1 << 5) | 1;
+        // 2020-09-13T12:26:40Z, その 1.5 秒後, 1970-01-01T00:00:00Z
+        let unix_epoch = 116_444_736_000_000_000u64;
+        let modified = unix_epoch + 1_600_000_000 * 10_000_000;
+        cdh.extra_field = vec![0x0a, 0x00, 32, 0, 0, 0, 0, 0, 0x01, 0x00, 24, 0];
+        for filetime in [modified, modified + 15_000_000, unix_epoch] {
+            cdh.extra_field.extend_from_slice(&filetime.to_le_bytes());
+        }
+        // NTFS のほうを優先する
+        cdh.extra_field.extend_from_slice(&[0x55, 0x54, 5, 0, 0x01, 0, 0, 0, 0]);
+
+        let timestamps = cdh.timestamps();
+        assert_eq!(timestamps.modified, Some(1_600_000_000_000));
+        assert_eq!(timestamps.accessed, Some(1_600_000_001_500));
+        assert_eq!(timestamps.created, Some(0));
+    }
+
+    #[test]
+    fn test_ntfs_extra_field_missing_times() {
+        let mut cdh = create_cdh_for_test("a.txt", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        cdh.last_mod_file_date = (1 << 5) | 1;
+        // 更新日時とアクセス日時が 0 の NTFS フィールド
+        let unix_epoch = 116_444_736_000_000_000u64;
+        cdh.extra_field = vec![0x0a, 0x00, 32, 0, 0, 0, 0, 0, 0x01, 0x00, 24, 0];
+        for filetime in [0, 0, unix_epoch] {
+            cdh.extra_field.extend_from_slice(&filetime.to_le_bytes());
+        }
+        // 更新日時は拡張タイムスタンプから補う
+        cdh.extra_field.extend_from_slice(&[0x55, 0x54, 5, 0, 0x01]);
+        cdh.extra_field.extend_from_slice(&1_600_000_000i32.to_le_bytes());
+
+        let timestamps = cdh.timestamps();
+        assert_eq!(timestamps.modified, Some(1_600_000_000_000));
+        assert_eq!(timestamps.accessed, None);
+        assert_eq!(timestamps.created, Some(0));
+
+        // 拡張タイムスタンプもなければ DOS の日時を使う
+        cdh.extra_field.truncate(36);
+        let timestamps = cdh.timestamps();
+        assert_eq!(timestamps.modified, Some(315532800000));
+        assert_eq!(timestamps.accessed, None);
+        assert_eq!(timestamps.created, Some(0));
+    }
+
+    #[test]
+    fn test_timestamps_fall_back_to_dos() {
+        let mut cdh = create_cdh_for_test("a.txt", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        cdh.last_mod_file_date = (1 << 5) | 1;
+        let timestamps = cdh.timestamps();
+        assert_eq!(timestamps.modified, Some(315532800000));
+        assert_eq!(timestamps.accessed, None);
+        assert_eq!(timestamps.created, None);
+    }
+
+    #[test]
+    fn test_cd_header_metadata() {
+        let mut cdh = create_cdh_for_test("dir/", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        cdh.file_comment = "コメント".as_bytes().to_vec();
+        assert!(cdh.is_dir());
+        assert_eq!(cdh.decoded_comment(LegacyEncoding::default()), "コメント");
+
+        let cdh = create_cdh_for_test("dir/file.txt", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        assert!(!cdh.is_dir());
+    }
+
+    #[test]
+    fn test_cd_header_unix_attributes() {
+        let mut cdh = create_cdh_for_test("bin/run", 0, 0, 0, COMPRESSION_METHOD_STORED);
+        cdh.version_made_by = (3 << 8) | 30;
+        cdh.external_file_attributes = 0o100755 << 16;
+        assert_eq!(cdh.host_os(), HOST_OS_UNIX);
+        assert_eq!(cdh.unix_mode(), Some(0o100755));
+        assert!(!cdh.is_dir());
+        assert!(!cdh.is_symlink());
+
+        cdh.external_file_attributes = 0o120777 << 16;
+        assert!(cdh.is_symlink());
+
+        // 名前が '/' で終わっていないディレクトリ
+        cdh.external_file_attributes = (0o040755 << 16) | DOS_ATTRIBUTE_DIRECTORY;
+        assert!(cdh.is_dir());
+
+        // MS-DOS で作成された場合は st_mode を持たない
+        cdh.version_made_by = 20;
+        cdh.external_file_attributes = DOS_ATTRIBUTE_DIRECTORY;
+        assert_eq!(cdh.host_os(), 0);
+        assert_eq!(cdh.unix_mode(), None);
+        assert!(cdh.is_dir());
+        assert!(!cdh.is_symlink());
+    }
+
+    #[test]
+    fn test_resolve_symlink_target() {
+        assert_eq!(resolve_symlink_target("a/b/link", "c.txt").as_deref(), Some("a/b/c.txt"));
+        assert_eq!(resolve_symlink_target("a/b/link", "../c/./d.txt").as_deref(), Some("a/c/d.txt"));
+        assert_eq!(resolve_symlink_target("link", "dir/").as_deref(), Some("dir"));
+        // アーカイブの外を指すもの
+        assert_eq!(resolve_symlink_target("a/link", "../../etc/passwd"), None);
+        assert_eq!(resolve_symlink_target("link", "/etc/passwd"), None);
+        assert_eq!(resolve_symlink_target("a/link", ".."), None);
+    }
+
+    // ===== Deflate64 tests =====
+
+    // Helper: DEFLATE のビット列を LSB から詰めて書き出す
+    #[cfg(feature = "deflate64")]
+    struct BitWriter {
+        out: Vec<u8>,
+        acc: u32,
+        count: u32,
+    }
+
+    #[cfg(feature = "deflate64")]
+    impl BitWriter {
+        fn new() -> Self {
+            BitWriter { out: Vec::new(), acc: 0, count: 0 }
+        }
+
+        fn bits(&mut self, value: u32, count: u32) {
+            for i in 0..count {
+                self.acc |= ((value >> i) & 1) << self.count;
+                self.count += 1;
+                if self.count == 8 {
+                    self.align();
+                }
+            }
+        }
+
+        // ハフマン符号は MSB から詰める
+        fn code(&mut self, code: u32, length: u32) {
+            for i in (0..length).rev() {
+                self.bits((code >> i) & 1, 1);
+            }
+        }
+
+        fn align(&mut self) {
+            if self.count > 0 {
+                self.out.push(self.acc as u8);
+                self.acc = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    // Helper: Deflate64 固有の符号を使ったストリームと展開結果を生成。
+    // 32KiB を超える距離（距離符号 30）と 16 ビット拡張の長さ符号 285 を含む
+    #[cfg(feature = "deflate64")]
+    pub(crate) fn create_deflate64_fixture() -> (Vec<u8>, Vec<u8>) {
+        let stored: Vec<u8> = (0..40000u32).map(|i| (i % 253) as u8).collect();
+        let mut expected = stored.clone();
+        let mut w = BitWriter::new();
+
+        // 非圧縮ブロック
+        w.bits(0, 1); // BFINAL
+        w.bits(0, 2); // BTYPE = stored
+        w.align();
+        w.out.extend_from_slice(&(stored.len() as u16).to_le_bytes());
+        w.out.extend_from_slice(&(!(stored.len() as u16)).to_le_bytes());
+        w.out.extend_from_slice(&stored);
+
+        // 固定ハフマンブロック
+        w.bits(1, 1); // BFINAL
+        w.bits(1, 2); // BTYPE = fixed
+        // 長さ 3 (符号 257)、距離 40000 (符号 30 + 14 ビット)
+        w.code(1, 7);
+        w.code(30, 5);
+        w.bits(40000 - 32769, 14);
+        expected.extend_from_slice(&stored[..3]);
+        // 長さ 1000 (符号 285 + 16 ビット)、距離 1 (符号 0)
+        w.code(0xC0 + 5, 8);
+        w.bits(1000 - 3, 16);
+        w.code(0, 5);
+        let last = *expected.last().unwrap();
+        expected.extend(std::iter::repeat_n(last, 1000));
+        // End of block
+        w.code(0, 7);
+        w.align();
+
+        (w.out, expected)
+    }
+
+    #[cfg(feature = "deflate64")]
+    #[test]
+    fn test_load_file_deflate64() {
+        let (compressed_data, original_data) = create_deflate64_fixture();
+        let crc32 = crc32fast::hash(&original_data);
+        let mut lfh = create_local_file_header_deflated(
+            "test.bin",
+            &compressed_data,
+            original_data.len() as u32,
+            crc32,
+        );
+        lfh[8..10].copy_from_slice(&COMPRESSION_METHOD_DEFLATE64.to_le_bytes());
+        let cdh = create_cdh_for_test(
+            "test.bin",
+            compressed_data.len() as u64,
+            original_data.len() as u64,
+            crc32,
+            COMPRESSION_METHOD_DEFLATE64,
+        );
+
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, None);
+        assert_eq!(result.unwrap(), original_data);
+    }
+
+    // ===== optional compression method tests =====
+
+    #[cfg(any(feature = "bzip2", feature = "lzma", feature = "zstd", feature = "xz"))]
+    fn create_compression_fixture() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..2000 {
+            data.extend_from_slice(format!("line {} of the round-trip fixture\n", i % 37).as_bytes());
+        }
+        data
+    }
+
+    // Helper: 圧縮済みデータをローカルヘッダに包んで load_file で展開する
+    #[cfg(any(feature = "bzip2", feature = "lzma", feature = "zstd", feature = "xz"))]
+    fn load_compressed(
+        compressed_data: &[u8],
+        original_data: &[u8],
+        compression_method: u16,
+        general_purpose_bit_flag: u16,
+    ) -> Result<Vec<u8>, LoadFileError> {
+        let crc32 = crc32fast::hash(original_data);
+        let mut lfh = create_local_file_header_deflated(
+            "test.txt",
+            compressed_data,
+            original_data.len() as u32,
+            crc32,
+        );
+        lfh[6..8].copy_from_slice(&((1u16 << 11) | general_purpose_bit_flag).to_le_bytes());
+        lfh[8..10].copy_from_slice(&compression_method.to_le_bytes());
+        let mut cdh = create_cdh_for_test(
+            "test.txt",
+            compressed_data.len() as u64,
+            original_data.len() as u64,
+            crc32,
+            compression_method,
+        );
+        cdh.general_purpose_bit_flag |= general_purpose_bit_flag;
+        load_file(io::Cursor::new(lfh), &cdh, true, None)
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn test_load_file_bzip2() {
+        use bzip2::write::BzEncoder;
+        use std::io::Write;
+
+        let original_data = create_compression_fixture();
+        let mut encoder = BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(&original_data).unwrap();
+        let compressed_data = encoder.finish().unwrap();
+
+        let result = load_compressed(&compressed_data, &original_data, COMPRESSION_METHOD_BZIP2, 0);
+        assert_eq!(result.unwrap(), original_data);
+    }
+
+    #[cfg(feature = "lzma")]
+    #[test]
+    fn test_load_file_lzma() {
+        let original_data = create_compression_fixture();
+        let mut lzma_data = Vec::new();
+        lzma_rs::lzma_compress(&mut io::Cursor::new(&original_data), &mut lzma_data).unwrap();
+
+        // .lzma 形式 (プロパティ 5 バイト + 展開後サイズ 8 バイト + データ) を ZIP の形式に詰め替える
+        let mut compressed_data = vec![0x10, 0x02, 0x05, 0x00];
+        compressed_data.extend_from_slice(&lzma_data[..5]);
+        compressed_data.extend_from_slice(&lzma_data[13..]);
+
+        // lzma-rs は終端マーカーを書き出すので bit 1 を立てる
+        let result = load_compressed(&compressed_data, &original_data, COMPRESSION_METHOD_LZMA, 1 << 1);
+        assert_eq!(result.unwrap(), original_data);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_load_file_zstd() {
+        use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+        let original_data = create_compression_fixture();
+        let compressed_data = compress_to_vec(&original_data[..], CompressionLevel::Fastest);
+
+        let result = load_compressed(&compressed_data, &original_data, COMPRESSION_METHOD_ZSTD, 0);
+        assert_eq!(result.unwrap(), original_data);
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_load_file_xz() {
+        let original_data = create_compression_fixture();
+        let mut compressed_data = Vec::new();
+        lzma_rs::xz_compress(&mut io::Cursor::new(&original_data), &mut compressed_data).unwrap();
+
+        let result = load_compressed(&compressed_data, &original_data, COMPRESSION_METHOD_XZ, 0);
+        assert_eq!(result.unwrap(), original_data);
+    }
+
+    // ===== ZipCrypto tests =====
+
+    // Helper: ZipCrypto で暗号化した非圧縮エントリのローカルヘッダと CD ヘッダを生成
+    fn create_zipcrypto_entry(content: &[u8], password: &[u8]) -> (Vec<u8>, CDHeader) {
+        use crate::zipcrypto::tests::encrypt;
+
+        let crc32 = crc32fast::hash(content);
+        let encrypted = encrypt(content, password, (crc32 >> 24) as u8);
+        let mut lfh = create_local_file_header("secret.txt", &encrypted, COMPRESSION_METHOD_STORED, crc32, true);
+        lfh[6..8].copy_from_slice(&((1u16 << 11) | 1).to_le_bytes());
+        // 非圧縮サイズは暗号化ヘッダを含まない
+        lfh[22..26].copy_from_slice(&(content.len() as u32).to_le_bytes());
+
+        let mut cdh = create_cdh_for_test(
+            "secret.txt",
+            encrypted.len() as u64,
+            content.len() as u64,
+            crc32,
+            COMPRESSION_METHOD_STORED,
+        );
+        cdh.general_purpose_bit_flag |= 1;
+        cdh.is_encrypted = true;
+        (lfh, cdh)
+    }
+
+    #[test]
+    fn test_load_file_zipcrypto() {
+        let (lfh, cdh) = create_zipcrypto_entry(b"Hello", b"secret");
+
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, Some(b"secret"));
+        assert_eq!(result.unwrap(), b"Hello");
+    }
+
+    #[test]
+    fn test_load_file_zipcrypto_password_errors() {
+        let (lfh, cdh) = create_zipcrypto_entry(b"Hello", b"secret");
+
+        let result = load_file(io::Cursor::new(lfh.clone()), &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::PasswordRequired)));
+
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, Some(b"wrong"));
+        assert!(matches!(result, Err(LoadFileError::WrongPassword)));
+    }
+
+    // ===== WinZip AES tests =====
+
+    // Helper: WinZip AES 拡張フィールドを生成
+    fn create_aes_extra_field(vendor_version: u16, strength: u8, compression_method: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&EXTRA_FIELD_AES.to_le_bytes());
+        data.extend_from_slice(&7u16.to_le_bytes());
+        data.extend_from_slice(&vendor_version.to_le_bytes());
+        data.extend_from_slice(b"AE");
+        data.push(strength);
+        data.extend_from_slice(&compression_method.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_cd_aes_extra_field() {
+        let mut cd_data = create_cd_header("secret.txt", 0, 10, 10, 0, COMPRESSION_METHOD_AES, true);
+        let extra = create_aes_extra_field(2, 3, COMPRESSION_METHOD_DEFLATED);
+        cd_data[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        cd_data.extend(extra);
+
+        let headers = parse_cd(&mut io::Cursor::new(cd_data), 1, Some(LegacyEncoding::default())).unwrap().0;
+        let aes = headers[0].aes.unwrap();
+        assert_eq!(aes.vendor_version, 2);
+        assert_eq!(aes.strength, 3);
+        assert_eq!(aes.compression_method, COMPRESSION_METHOD_DEFLATED);
+    }
+
+    #[test]
+    fn test_parse_cd_malformed_aes_extra_field() {
+        // データ部分が短すぎる AES 拡張フィールド
+        let mut cd_data = create_cd_header("secret.txt", 0, 10, 10, 0, COMPRESSION_METHOD_AES, true);
+        let extra = [0x01, 0x99, 0x03, 0x00, 0x02, 0x00, 0x41];
+        cd_data[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        cd_data.extend(extra);
+        cd_data.extend(create_cd_header("plain.txt", 100, 5, 5, 0, COMPRESSION_METHOD_STORED, true));
+
+        // 他のエントリは読める
+        let headers = parse_cd(&mut io::Cursor::new(cd_data), 2, Some(LegacyEncoding::default())).unwrap().0;
+        assert!(headers[0].aes.is_none());
+        assert_eq!(headers[1].file_name, "plain.txt");
+
+        let mut cdh = create_cdh_for_test("secret.txt", 5, 5, 0, COMPRESSION_METHOD_AES);
+        cdh.general_purpose_bit_flag |= 1;
+        cdh.is_encrypted = true;
+        let mut lfh = create_local_file_header("secret.txt", b"xxxxx", COMPRESSION_METHOD_AES, 0, true);
+        lfh[6] |= 1;
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, Some(b"secret"));
+        assert!(matches!(result, Err(LoadFileError::InvalidAesExtraField)));
+    }
+
+    #[test]
+    fn test_load_file_aes_deflated() {
+        use crate::winzip_aes::tests::encrypt;
+        use miniz_oxide::deflate::compress_to_vec;
+
+        let original_data = b"Hello, World! This is a test for AES encrypted entries.";
+        let compressed_data = compress_to_vec(original_data, 6);
+        let encrypted = encrypt(&compressed_data, b"secret", 3);
+
+        // AE-2 は CRC を 0 にする
+        let mut lfh = create_local_file_header_deflated("secret.txt", &encrypted, original_data.len() as u32, 0);
+        lfh[6..8].copy_from_slice(&((1u16 << 11) | 1).to_le_bytes());
+        lfh[8..10].copy_from_slice(&COMPRESSION_METHOD_AES.to_le_bytes());
+        let mut cdh = create_cdh_for_test(
+            "secret.txt",
+            encrypted.len() as u64,
+            original_data.len() as u64,
+            0,
+            COMPRESSION_METHOD_AES,
+        );
+        cdh.general_purpose_bit_flag |= 1;
+        cdh.is_encrypted = true;
+        cdh.aes = Some(AesExtraField {
+            vendor_version: 2,
+            strength: 3,
+            compression_method: COMPRESSION_METHOD_DEFLATED,
+        });
+
+        let result = load_file(io::Cursor::new(lfh.clone()), &cdh, true, Some(b"secret"));
+        assert_eq!(result.unwrap(), original_data);
+
+        let result = load_file(io::Cursor::new(lfh.clone()), &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::PasswordRequired)));
+
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, Some(b"wrong"));
+        assert!(matches!(result, Err(LoadFileError::WrongPassword)));
+    }
+
+    // ===== Info-ZIP Unicode extra field tests =====
+
+    // Helper: Info-ZIP Unicode Path / Comment 拡張フィールドを生成
+    fn create_unicode_extra_field(header_id: u16, original: &[u8], value: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&header_id.to_le_bytes());
+        data.extend_from_slice(&((5 + value.len()) as u16).to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&crc32fast::hash(original).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        data
+    }
+
+    // Helper: UTF-8 フラグのない CD ヘッダに拡張フィールドとコメントを付ける
+    fn create_cd_header_with_extra(name: &[u8], extra: &[u8], comment: &[u8]) -> Vec<u8> {
+        let placeholder = "_".repeat(name.len());
+        let mut data = create_cd_header(&placeholder, 0, 0, 0, 0, COMPRESSION_METHOD_STORED, false);
+        data[46..].copy_from_slice(name);
+        data[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        data[32..34].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+        data.extend_from_slice(extra);
+        data.extend_from_slice(comment);
+        data
+    }
+
+    #[test]
+    fn test_parse_cd_unicode_path() {
+        // CP437 では "Gr÷▀e.txt" と読めてしまう名前
+        let name = [0x47, 0x72, 0xF6, 0xDF, 0x65, 0x2E, 0x74, 0x78, 0x74];
+        let comment = b"caf\x82";
+        let mut extra = create_unicode_extra_field(EXTRA_FIELD_UNICODE_PATH, &name, "Größe.txt");
+        extra.extend(create_unicode_extra_field(EXTRA_FIELD_UNICODE_COMMENT, comment, "café ☕"));
+        let cd_data = create_cd_header_with_extra(&name, &extra, comment);
+
+        let (headers, _) = parse_cd(&mut io::Cursor::new(cd_data), 1, None).unwrap();
+        assert_eq!(headers[0].file_name, "Größe.txt");
+        assert_eq!(headers[0].file_name_bytes, name);
+        assert_eq!(headers[0].decoded_comment(LegacyEncoding::default()), "café ☕");
+    }
+
+    #[test]
+    fn test_parse_cd_unicode_path_crc_mismatch() {
+        let name = b"new.txt";
+        // 元の名前が書き換えられ、CRC-32 が一致しない
+        let extra = create_unicode_extra_field(EXTRA_FIELD_UNICODE_PATH, b"old.txt", "古い.txt");
+        let cd_data = create_cd_header_with_extra(name, &extra, b"caf\x82");
+
+        let (headers, _) = parse_cd(&mut io::Cursor::new(cd_data), 1, None).unwrap();
+        assert_eq!(headers[0].file_name, "new.txt");
+        assert_eq!(headers[0].decoded_comment(LegacyEncoding::default()), "café");
+    }
+
+    // ===== data descriptor tests =====
+
+    // Helper: データディスクリプタを生成
+    fn create_data_descriptor(signed: bool, wide: bool, crc32: u32, compressed_size: u64, uncompressed_size: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        if signed {
+            data.extend_from_slice(&DATA_DESCRIPTOR_SIGNATURE.to_le_bytes());
+        }
+        data.extend_from_slice(&crc32.to_le_bytes());
+        if wide {
+            data.extend_from_slice(&compressed_size.to_le_bytes());
+            data.extend_from_slice(&uncompressed_size.to_le_bytes());
+        } else {
+            data.extend_from_slice(&(compressed_size as u32).to_le_bytes());
+            data.extend_from_slice(&(uncompressed_size as u32).to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_data_descriptor_matrix() {
+        let crc32 = 0xF7D18982u32;
+        let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        let expected = DataDescriptor {
+            crc32,
+            compressed_size: 5,
+            uncompressed_size: 5,
+        };
+        for signed in [true, false] {
+            for wide in [true, false] {
+                for zip64 in [true, false] {
+                    for padding in [&[][..], &[0u8; 7][..], &LFH_SIGNATURE.to_le_bytes()[..]] {
+                        let mut buf = create_data_descriptor(signed, wide, crc32, 5, 5);
+                        buf.extend_from_slice(padding);
+                        let descriptor = parse_data_descriptor(&buf, &cdh, zip64);
+                        assert_eq!(
+                            descriptor,
+                            Some(expected),
+                            "signed: {}, wide: {}, zip64: {}, padding: {}",
+                            signed,
+                            wide,
+                            zip64,
+                            padding.len()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_data_descriptor_crc_equals_signature() {
+        // CRC がシグネチャと同じ値で、シグネチャがない
+        let crc32 = DATA_DESCRIPTOR_SIGNATURE;
+        let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        let buf = create_data_descriptor(false, false, crc32, 5, 5);
+        let descriptor = parse_data_descriptor(&buf, &cdh, false).unwrap();
+        assert_eq!(descriptor.crc32, crc32);
+        assert_eq!(descriptor.compressed_size, 5);
+    }
+
+    #[test]
+    fn test_parse_data_descriptor_mismatch_and_truncated() {
+        let cdh = create_cdh_for_test("test.txt", 5, 5, 0x12345678, COMPRESSION_METHOD_STORED);
+        // CD と一致しない場合は優先する形式で読んだ値
+        let buf = create_data_descriptor(true, false, 0xDEADBEEF, 5, 5);
+        let descriptor = parse_data_descriptor(&buf, &cdh, false).unwrap();
+        assert_eq!(descriptor.crc32, 0xDEADBEEF);
+        // どの形式にも足りない
+        assert_eq!(parse_data_descriptor(&[0x50, 0x4b, 0x07], &cdh, false), None);
+    }
+
+    #[test]
+    fn test_load_file_data_descriptor_variants() {
+        let file_content = b"Hello";
+        let crc32 = 0xF7D18982u32;
+        for signed in [true, false] {
+            for wide in [true, false] {
+                let mut lfh = create_local_file_header("test.txt", file_content, COMPRESSION_METHOD_STORED, 0, true);
+                // ディスクリプタを使う場合、ローカルヘッダの CRC とサイズは 0
+                lfh[6..8].copy_from_slice(&((1u16 << 11) | (1 << 3)).to_le_bytes());
+                lfh[18..26].fill(0);
+                lfh.extend(create_data_descriptor(signed, wide, crc32, 5, 5));
+                lfh.extend_from_slice(&[0u8; 3]);
+
+                let mut cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+                cdh.general_purpose_bit_flag |= 1 << 3;
+
+                let result = load_file(io::Cursor::new(lfh), &cdh, true, None);
+                assert_eq!(result.unwrap(), b"Hello", "signed: {}, wide: {}", signed, wide);
+            }
+        }
+
+        // 範囲がディスクリプタの手前で終わっている
+        let mut lfh = create_local_file_header("test.txt", file_content, COMPRESSION_METHOD_STORED, 0, true);
+        lfh[6..8].copy_from_slice(&((1u16 << 11) | (1 << 3)).to_le_bytes());
+        let mut cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        cdh.general_purpose_bit_flag |= 1 << 3;
+        let result = load_file(io::Cursor::new(lfh), &cdh, true, None);
+        assert_eq!(result.unwrap(), b"Hello");
+    }
+
+    // ===== load_file_from_chunk tests =====
+
+    #[test]
+    fn test_load_file_from_chunk() {
+        let crc32 = 0xF7D18982u32;
+        let lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, crc32, true);
+        let mut cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        cdh.relative_offset_of_local_header = 1000;
+
+        // ファイル上の 900 バイト目から始まり、エントリの後ろに次のエントリが続く断片
+        let mut chunk = vec![0u8; 100];
+        chunk.extend_from_slice(&lfh);
+        chunk.extend(create_local_file_header("next.txt", b"World", COMPRESSION_METHOD_STORED, 0, true));
+
+        let result = load_file_from_chunk(&chunk, 900, 1000, &cdh, true, None);
+        assert_eq!(result.unwrap(), b"Hello");
+
+        // エントリの先頭から始まる断片
+        let result = load_file_from_chunk(&lfh, 1000, 1000, &cdh, true, None);
+        assert_eq!(result.unwrap(), b"Hello");
+    }
+
+    #[test]
+    fn test_load_file_from_chunk_not_covered() {
+        let crc32 = 0xF7D18982u32;
+        let lfh = create_local_file_header("test.txt", b"Hello", COMPRESSION_METHOD_STORED, crc32, true);
+        let cdh = create_cdh_for_test("test.txt", 5, 5, crc32, COMPRESSION_METHOD_STORED);
+        let mut chunk = vec![0u8; 100];
+        chunk.extend_from_slice(&lfh);
+
+        // 断片がエントリより後ろから始まる
+        let result = load_file_from_chunk(&chunk[1..], 1, 0, &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::ChunkNotCovered)));
+        // 断片がエントリより前で終わる
+        let result = load_file_from_chunk(&chunk[..50], 0, 100, &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::ChunkNotCovered)));
+        // データの途中で切れている
+        let result = load_file_from_chunk(&chunk[..chunk.len() - 1], 0, 100, &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::ChunkNotCovered)));
+        // ローカルヘッダではない位置
+        let result = load_file_from_chunk(&chunk, 0, 50, &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::UnmatchHeader)));
+        // 壊れた CD の巨大なオフセットやサイズ
+        let result = load_file_from_chunk(&chunk, 0, u64::MAX - 10, &cdh, true, None);
+        assert!(matches!(result, Err(LoadFileError::ChunkNotCovered)));
+        let huge = create_cdh_for_test("test.txt", u64::MAX, 5, crc32, COMPRESSION_METHOD_STORED);
+        let result = load_file_from_chunk(&chunk, 0, 100, &huge, true, None);
+        assert!(matches!(result, Err(LoadFileError::ChunkNotCovered)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use crate::zip::{CDHeader, LoadFileError};
+use std::io;
+
+const ENCRYPTION_HEADER_LENGTH: usize = 12;
+
+const CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32_update(crc: u32, b: u8) -> u32 {
+    CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+}
+
+// Traditional PKWARE Encryption (APPNOTE 6.1) の鍵
+pub struct ZipCryptoKeys {
+    key0: u32,
+    key1: u32,
+    key2: u32,
+}
+
+impl ZipCryptoKeys {
+    pub fn new(password: &[u8]) -> ZipCryptoKeys {
+        let mut keys = ZipCryptoKeys {
+            key0: 0x12345678,
+            key1: 0x23456789,
+            key2: 0x34567890,
+        };
+        for &b in password {
+            keys.update(b);
+        }
+        keys
+    }
+
+    fn update(&mut self, b: u8) {
+        self.key0 = crc32_update(self.key0, b);
+        self.key1 = self
+            .key1
+            .wrapping_add(self.key0 & 0xFF)
+            .wrapping_mul(134775813)
+            .wrapping_add(1);
+        self.key2 = crc32_update(self.key2, (self.key1 >> 24) as u8);
+    }
+
+    fn stream_byte(&self) -> u8 {
+        let temp = (self.key2 | 2) as u16;
+        (temp.wrapping_mul(temp ^ 1) >> 8) as u8
+    }
+
+    pub fn decrypt_byte(&mut self, b: u8) -> u8 {
+        let plain = b ^ self.stream_byte();
+        self.update(plain);
+        plain
+    }
+
+    #[cfg(test)]
+    pub fn encrypt_byte(&mut self, b: u8) -> u8 {
+        let cipher = b ^ self.stream_byte();
+        self.update(b);
+        cipher
+    }
+}
+
+// 暗号化ヘッダの最終バイトと照合する値。データディスクリプタを使う場合は CRC が
+// ヘッダ書き込み時点で分からないため、更新時刻の上位バイトが使われる
+pub fn check_byte(cdh: &CDHeader) -> u8 {
+    if cdh.general_purpose_bit_flag & (1 << 3) != 0 {
+        (cdh.last_mod_file_time >> 8) as u8
+    } else {
+        (cdh.crc32 >> 24) as u8
+    }
+}
+
+// 先頭 12 バイトの暗号化ヘッダを含むデータを復号し、ヘッダを除いた部分を返す
+pub fn decrypt(data: &[u8], password: &[u8], check_byte: u8) -> Result<Vec<u8>, LoadFileError> {
+    if data.len() < ENCRYPTION_HEADER_LENGTH {
+        return Result::Err(LoadFileError::IOError(io::ErrorKind::UnexpectedEof.into()));
+    }
+    let mut keys = ZipCryptoKeys::new(password);
+    let mut header = [0u8; ENCRYPTION_HEADER_LENGTH];
+    for (h, &b) in header.iter_mut().zip(&data[..ENCRYPTION_HEADER_LENGTH]) {
+        *h = keys.decrypt_byte(b);
+    }
+    if header[ENCRYPTION_HEADER_LENGTH - 1] != check_byte {
+        return Result::Err(LoadFileError::WrongPassword);
+    }
+    Result::Ok(
+        data[ENCRYPTION_HEADER_LENGTH..]
+            .iter()
+            .map(|&b| keys.decrypt_byte(b))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Helper: 暗号化ヘッダを付けて暗号化する
+    pub(crate) fn encrypt(data: &[u8], password: &[u8], check_byte: u8) -> Vec<u8> {
+        let mut keys = ZipCryptoKeys::new(password);
+        let mut header = [0x5Au8; ENCRYPTION_HEADER_LENGTH];
+        header[ENCRYPTION_HEADER_LENGTH - 1] = check_byte;
+        header
+            .iter()
+            .chain(data)
+            .map(|&b| keys.encrypt_byte(b))
+            .collect()
+    }
+
+    #[test]
+    fn test_crc32_table_matches_crc32fast() {
+        let crc = b"Hello".iter().fold(0xFFFFFFFF, |crc, &b| crc32_update(crc, b));
+        assert_eq!(!crc, crc32fast::hash(b"Hello"));
+    }
+
+    #[test]
+    fn test_decrypt_round_trip() {
+        let encrypted = encrypt(b"Hello, ZipCrypto!", b"secret", 0xF7);
+        let decrypted = decrypt(&encrypted, b"secret", 0xF7).unwrap();
+        assert_eq!(decrypted, b"Hello, ZipCrypto!");
+    }
+
+    #[test]
+    fn test_decrypt_wrong_password() {
+        let encrypted = encrypt(b"Hello, ZipCrypto!", b"secret", 0xF7);
+        let result = decrypt(&encrypted, b"wrong", 0xF7);
+        assert!(matches!(result, Err(LoadFileError::WrongPassword)));
+    }
+}